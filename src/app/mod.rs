@@ -1,30 +1,163 @@
+use std::fs::write;
+use std::path::Path;
+
 use anyhow::{Context, Result};
 use clap::{App, Arg, ArgMatches};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 
+use crate::capacity::{capacity, Capacity};
 use crate::config::app::AppConfiguration;
 use crate::config::edition::EditionConfiguration;
+use crate::config::uri::UriConfiguration;
+use crate::hashing::sha256_digest;
 use crate::layers_model::Layers;
-use crate::logger::log_measure;
-use crate::processor::create_images;
+use crate::logger::{log_error, log_info, log_measure, log_warn};
+use crate::processor::{create_images, update_uris};
+use crate::validator::{validate, Severity, ValidationIssue, ValidationReport};
 
 pub fn run() -> Result<()> {
     let context = "Run application";
 
     let matches = get_matches();
     let layer_config_file = matches.value_of("config").unwrap();
+    let app_config = AppConfiguration::from_arg_matches(&matches);
+
+    match matches.subcommand() {
+        Some(("validate", validate_matches)) => {
+            return run_validate(layer_config_file, &app_config, validate_matches).context(context)
+        }
+        Some(("capacity", capacity_matches)) => {
+            return run_capacity(layer_config_file, &app_config, capacity_matches).context(context)
+        }
+        Some(("update-uri", uri_matches)) => {
+            return run_update_uri(layer_config_file, &app_config, uri_matches).context(context)
+        }
+        _ => {}
+    }
 
     let edition_config = EditionConfiguration::try_from_path(layer_config_file).context(context)?;
-    let app_config = AppConfiguration::from_arg_matches(&matches);
     let layers = Layers::from_config(&app_config);
+    let mut rng = create_rng(app_config.get_seed());
 
     let log = log_measure("create images");
-    create_images(&layers, &edition_config, &app_config).context("asd")?;
+    create_images(&layers, &edition_config, &app_config, &mut rng).context(context)?;
     log.finish();
 
     Ok(())
 }
 
+fn run_validate<L: AsRef<Path>, D: AsRef<Path>>(
+    layer_config_file: &str,
+    app_config: &AppConfiguration<L, D>,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let context = "Validate layers and edition configuration";
+
+    let report = match EditionConfiguration::try_from_path(layer_config_file) {
+        Ok(edition_config) => {
+            let layers = Layers::from_config(app_config);
+
+            validate(&layers, &edition_config, app_config.get_dimensions())
+        }
+        Err(err) => ValidationReport::from(vec![ValidationIssue::error(format!("{:#}", err))]),
+    };
+
+    for issue in report.get_issues() {
+        match issue.get_severity() {
+            Severity::Error => log_error(issue.to_string()),
+            Severity::Warning => log_warn(issue.to_string()),
+        }
+    }
+
+    let report_json = serde_json::to_string_pretty(&report).context(context)?;
+    match matches.value_of("report") {
+        Some(report_file) => write(report_file, report_json).context(context)?,
+        None => println!("{}", report_json),
+    }
+
+    if !report.is_valid() {
+        bail!(
+            "Validation failed with {} errors and {} warnings",
+            report.get_errors(),
+            report.get_warnings()
+        );
+    }
+    log_info(format!(
+        "Validation succeeded with {} warnings",
+        report.get_warnings()
+    ));
+
+    Ok(())
+}
+
+fn run_capacity<L: AsRef<Path>, D: AsRef<Path>>(
+    layer_config_file: &str,
+    app_config: &AppConfiguration<L, D>,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let context = "Compute capacity of edition configuration";
+
+    let edition_config = EditionConfiguration::try_from_path(layer_config_file).context(context)?;
+    let layers = Layers::from_config(app_config);
+
+    let capacities = edition_config
+        .get_layers()
+        .iter()
+        .map(|layer_config| capacity(&layers, &edition_config, layer_config))
+        .collect::<Vec<Capacity>>();
+
+    for (index, capacity) in capacities.iter().enumerate() {
+        let message = format!("[layers #{}] {}", index, capacity.describe());
+
+        if capacity.is_exceeded() || capacity.is_critical() {
+            log_warn(message);
+        } else {
+            log_info(message);
+        }
+    }
+
+    if let Some(report_file) = matches.value_of("report") {
+        let report_json = serde_json::to_string_pretty(&capacities).context(context)?;
+        write(report_file, report_json).context(context)?;
+    }
+
+    Ok(())
+}
+
+fn run_update_uri<L: AsRef<Path>, D: AsRef<Path>>(
+    layer_config_file: &str,
+    app_config: &AppConfiguration<L, D>,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let context = "Update image URIs of edition metadata";
+
+    let edition_config = EditionConfiguration::try_from_path(layer_config_file).context(context)?;
+    let uri_config = UriConfiguration::from_arg_matches(matches);
+
+    let updated = update_uris(&edition_config, app_config, &uri_config).context(context)?;
+    log_info(format!("Updated URIs of {} editions", updated));
+
+    Ok(())
+}
+
+/// Seeded runs use ChaCha20, its output is stable across platforms and `rand` releases.
+fn create_rng(seed: Option<&str>) -> ChaCha20Rng {
+    match seed {
+        Some(seed) => {
+            log_info(format!("Use seed for image processing: {}", seed));
+
+            ChaCha20Rng::from_seed(sha256_digest(seed))
+        }
+        None => ChaCha20Rng::from_entropy(),
+    }
+}
+
 fn get_matches() -> ArgMatches {
+    build_app().get_matches()
+}
+
+pub(crate) fn build_app() -> App<'static> {
     App::new("Sdx NFT Art Engine")
         .arg(
             Arg::new("layer")
@@ -33,6 +166,7 @@ fn get_matches() -> ArgMatches {
                 .long("layer-dir")
                 .value_name("LAYER_DIR")
                 .takes_value(true)
+                .global(true)
                 .default_value("./layers"),
         )
         .arg(
@@ -42,6 +176,7 @@ fn get_matches() -> ArgMatches {
                 .long("config-file")
                 .value_name("CONFIG_FILE")
                 .takes_value(true)
+                .global(true)
                 .default_value("./config/layer_configuration.json"),
         )
         .arg(
@@ -51,6 +186,7 @@ fn get_matches() -> ArgMatches {
                 .long("destination-dir")
                 .value_name("DESTINATION_DIR")
                 .takes_value(true)
+                .global(true)
                 .default_value("./build"),
         )
         .arg(
@@ -67,6 +203,31 @@ fn get_matches() -> ArgMatches {
                 .help("Removes the destination directory and all of the content")
                 .short('t')
                 .long("cleanup"))
+        .arg(
+            Arg::new("resume")
+                .help("Continues an interrupted run, keeping all complete editions in the destination directory")
+                .long("resume")
+                .conflicts_with("cleanup"),
+        )
+        .arg(
+            Arg::new("starting-index-seed")
+                .help("Seed (e.g. a block hash) of the starting index offset in the provenance record")
+                .long("starting-index-seed")
+                .value_name("SEED")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("pinned")
+                .help("CSV or JSON file of editions with pinned traits (layer to file names or DNA)")
+                .long("pinned")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("ipfs")
+                .help("Computes the IPFS CIDs of the images, packs them into a CAR file and links them in the metadata")
+                .long("ipfs"),
+        )
         .arg(
             Arg::new("size")
                 .help("Image size (in px) of processed images")
@@ -74,7 +235,266 @@ fn get_matches() -> ArgMatches {
                 .long("size")
                 .value_name("SIZE")
                 .takes_value(true)
+                .global(true)
                 .default_value("1024"),
         )
-        .get_matches()
+        .arg(
+            Arg::new("width")
+                .help("Image width (in px) of processed images, defaults to the size")
+                .long("width")
+                .value_name("WIDTH")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::new("height")
+                .help("Image height (in px) of processed images, defaults to the size")
+                .long("height")
+                .value_name("HEIGHT")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::new("filter")
+                .help("Filter resampling layer images to the image size (nearest suits pixel art)")
+                .long("filter")
+                .value_name("FILTER")
+                .takes_value(true)
+                .possible_values(["nearest", "triangle", "catmull-rom", "gaussian", "lanczos3"])
+                .default_value("lanczos3"),
+        )
+        .arg(
+            Arg::new("extra-size")
+                .help("Additionally saves every image in this size (e.g. 256x256 for thumbnails), can be repeated")
+                .long("extra-size")
+                .value_name("WIDTHxHEIGHT")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::new("seed")
+                .help("Seed for the random generator, the same seed, layers and configuration create the same editions")
+                .long("seed")
+                .value_name("SEED")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("jobs")
+                .help("Number of worker threads compositing and encoding images (0 uses all logical CPUs)")
+                .short('j')
+                .long("jobs")
+                .value_name("JOBS")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("cache-size")
+                .help("Memory cap (in MB) for decoded layer images shared across editions (0 disables the cache)")
+                .long("cache-size")
+                .value_name("CACHE_SIZE")
+                .takes_value(true)
+                .default_value("512"),
+        )
+        .arg(
+            Arg::new("format")
+                .help("Format of processed images")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(["png", "webp", "jpeg"])
+                .default_value("png"),
+        )
+        .arg(
+            Arg::new("quality")
+                .help("Quality (0-100) of lossy webp and jpeg images")
+                .long("quality")
+                .value_name("QUALITY")
+                .takes_value(true)
+                .default_value("90"),
+        )
+        .arg(
+            Arg::new("lossless")
+                .help("Encodes webp images lossless")
+                .long("lossless"),
+        )
+        .arg(
+            Arg::new("png-compression")
+                .help("Compression level of png images")
+                .long("png-compression")
+                .value_name("LEVEL")
+                .takes_value(true)
+                .possible_values(["fast", "default", "best"])
+                .default_value("default"),
+        )
+        .arg(
+            Arg::new("palette")
+                .help("Quantizes png images to an indexed palette with this number of colors (2-256)")
+                .long("palette")
+                .value_name("COLORS")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("background")
+                .help("Background color (#rrggbb) of jpeg images, which have no transparency")
+                .long("background")
+                .value_name("COLOR")
+                .takes_value(true)
+                .default_value("#ffffff"),
+        )
+        .arg(
+            Arg::new("animation-format")
+                .help("Format of animations, written for editions with animated layers")
+                .long("animation-format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(["gif", "apng", "webp"])
+                .default_value("gif"),
+        )
+        .arg(
+            Arg::new("frame-delay")
+                .help("Delay (in ms) of animation frames without an own delay, e.g. frame directories")
+                .long("frame-delay")
+                .value_name("MS")
+                .takes_value(true)
+                .global(true)
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("frame-strategy")
+                .help("How animated layers with different frame counts or timings are combined")
+                .long("frame-strategy")
+                .value_name("STRATEGY")
+                .takes_value(true)
+                .possible_values(["timeline", "longest"])
+                .default_value("timeline"),
+        )
+        .arg(
+            Arg::new("max-frames")
+                .help("Maximum number of frames of an animation, longer animations are cut off")
+                .long("max-frames")
+                .value_name("MAX_FRAMES")
+                .takes_value(true)
+                .default_value("500"),
+        )
+        .subcommand(
+            App::new("validate")
+                .about("Validates the layers directory and edition configuration without creating images")
+                .arg(
+                    Arg::new("report")
+                        .help("Writes the validation report (JSON) to the file instead of stdout")
+                        .short('r')
+                        .long("report")
+                        .value_name("REPORT_FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("capacity")
+                .alias("stats")
+                .about("Computes how many distinct editions every layer configuration can produce")
+                .arg(
+                    Arg::new("report")
+                        .help("Writes the capacity report (JSON) to the file")
+                        .short('r')
+                        .long("report")
+                        .value_name("REPORT_FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("update-uri")
+                .about("Rewrites the image and animation URIs of existing metadata, e.g. after uploading the images")
+                .arg(
+                    Arg::new("image-uri")
+                        .help("New base URI or URI template ({cid}, {edition}, {extension}) of the images")
+                        .long("image-uri")
+                        .value_name("URI")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("animation-uri")
+                        .help("New base URI or URI template of the animations")
+                        .long("animation-uri")
+                        .value_name("URI")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("cid")
+                        .help("Content identifier replacing {cid} in the URI templates")
+                        .long("cid")
+                        .value_name("CID")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output-dir")
+                        .help("Writes the updated metadata into this directory instead of updating it in place")
+                        .short('o')
+                        .long("output-dir")
+                        .value_name("OUTPUT_DIR")
+                        .takes_value(true),
+                ),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{arg_matches, test_config, write_json, TempDir};
+
+    #[test]
+    fn same_seed_creates_same_editions() {
+        let dir = TempDir::with_layers(
+            "seed",
+            &[
+                ("Background", &["Blue#10", "Green", "Red#5"]),
+                ("Eyes", &["Big#3", "Small"]),
+                ("Hat", &["Cap", "Crown#2", "None#8"]),
+            ],
+        );
+        let layers_dir = dir.join("layers");
+        let config = dir.join("config.json");
+        write_json(
+            &config,
+            &test_config(json!({
+                "layers": [{
+                    "size": 8,
+                    "order": [{"name": "Background"}, {"name": "Eyes"}, {"name": "Hat"}]
+                }]
+            })),
+        );
+        let edition_config = EditionConfiguration::try_from_path(&config).unwrap();
+
+        let run = |destination: &str| {
+            let destination = dir.join(destination);
+            let matches = arg_matches(&[
+                "-l",
+                layers_dir.to_str().unwrap(),
+                "-d",
+                destination.to_str().unwrap(),
+                "-s",
+                "4",
+                "--seed",
+                "same seed",
+            ]);
+            let app_config = AppConfiguration::from_arg_matches(&matches);
+            let layers = Layers::from_config(&app_config);
+            let mut rng = create_rng(app_config.get_seed());
+            create_images(&layers, &edition_config, &app_config, &mut rng).unwrap();
+
+            (1..=8)
+                .map(|edition| {
+                    read_to_string(destination.join("meta").join(format!("{}.json", edition)))
+                        .unwrap()
+                })
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(run("first"), run("second"));
+    }
 }
@@ -0,0 +1,310 @@
+use serde::Serialize;
+
+use crate::config::edition::{EditionConfiguration, LayerConfiguration, LayerOrderConfiguration};
+use crate::layers_model::Layers;
+
+/// Maximum number of visited combinations, before counting the combinations allowed by the
+/// rules stops and only a lower bound is reported.
+const ENUMERATION_BUDGET: u64 = 10_000_000;
+
+/// Share of the capacity an edition size may use before duplicate retries become likely.
+const CAPACITY_WARN_RATIO: f64 = 0.8;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capacity {
+    size: u32,
+    /// Distinct DNAs the layer configuration can produce, a lower bound if not exact.
+    combinations: u128,
+    /// Distinct DNAs without considering any rule.
+    upper_bound: u128,
+    exact: bool,
+}
+
+impl Capacity {
+    /// Whether the edition size can't be produced for sure.
+    pub fn is_exceeded(&self) -> bool {
+        self.size as u128 > self.upper_bound
+            || (self.exact && self.size as u128 > self.combinations)
+    }
+
+    /// Whether the edition size is close to (or possibly above) the capacity.
+    pub fn is_critical(&self) -> bool {
+        self.size as f64 >= self.combinations as f64 * CAPACITY_WARN_RATIO
+    }
+
+    pub fn describe(&self) -> String {
+        let combinations = if self.exact {
+            self.combinations.to_string()
+        } else {
+            format!("between {} and {}", self.combinations, self.upper_bound)
+        };
+
+        format!(
+            "Edition size {} of {} possible combinations ({:.2}% used)",
+            self.size,
+            combinations,
+            self.size as f64 / self.combinations.max(1) as f64 * 100.0
+        )
+    }
+}
+
+/// Computes the number of distinct DNAs the layer configuration can produce. Layers without any
+/// rule are counted via binomial coefficients, layers involved in exclusion or requirement
+/// rules are enumerated.
+pub fn capacity(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    layer_config: &LayerConfiguration,
+) -> Capacity {
+    let order = layer_config.get_order();
+    let mut upper_bound = 1u128;
+    let mut independent = 1u128;
+    let mut dependent = vec![];
+
+    for lo in order {
+        let file_count = layers
+            .get_file_names(lo.get_name())
+            .map_or(0, |f| f.len() as u32);
+        let combinations = pick_combinations(file_count, lo.get_pick_min(), lo.get_pick_max());
+        upper_bound = upper_bound.saturating_mul(combinations);
+
+        if is_rule_involved(edition_config, order, lo) {
+            dependent.push(lo);
+        } else {
+            independent = independent.saturating_mul(combinations);
+        }
+    }
+
+    let mut counter = Counter {
+        count: 0,
+        visited: 0,
+    };
+    let exact = count_combinations(
+        layers,
+        edition_config,
+        &dependent,
+        &mut vec![],
+        &mut counter,
+    );
+
+    Capacity {
+        size: layer_config.get_size(),
+        combinations: counter.count.saturating_mul(independent),
+        upper_bound,
+        exact,
+    }
+}
+
+/// Distinct DNAs (without considering any rule) containing a single, given file of the layer.
+pub fn combinations_with_file(
+    layers: &Layers,
+    layer_config: &LayerConfiguration,
+    layer: &str,
+) -> u128 {
+    layer_config
+        .get_order()
+        .iter()
+        .filter(|lo| lo.get_name() != layer)
+        .map(|lo| {
+            let file_count = layers
+                .get_file_names(lo.get_name())
+                .map_or(0, |f| f.len() as u32);
+
+            pick_combinations(file_count, lo.get_pick_min(), lo.get_pick_max())
+        })
+        .fold(1u128, |product, c| product.saturating_mul(c))
+}
+
+struct Counter {
+    count: u128,
+    visited: u64,
+}
+
+/// Walks the layer order the same way images are created and counts the valid combinations,
+/// returns false if the enumeration budget is exhausted.
+fn count_combinations<'a>(
+    layers: &'a Layers,
+    edition_config: &EditionConfiguration,
+    order: &[&'a LayerOrderConfiguration],
+    picked: &mut Vec<(&'a str, &'a str)>,
+    counter: &mut Counter,
+) -> bool {
+    let (lo, rest) = match order.split_first() {
+        Some(split) => split,
+        None => {
+            counter.count += 1;
+            return true;
+        }
+    };
+
+    counter.visited += 1;
+    if counter.visited > ENUMERATION_BUDGET {
+        return false;
+    }
+
+    let only_if = lo.get_only_if();
+    let is_drawn = only_if.is_empty()
+        || only_if
+            .iter()
+            .any(|s| picked.iter().any(|(layer, name)| s.matches(layer, name)));
+    let file_names = match layers.get_file_names(lo.get_name()) {
+        Some(file_names) if is_drawn => file_names,
+        _ => return count_combinations(layers, edition_config, rest, picked, counter),
+    };
+
+    let candidates = file_names
+        .into_iter()
+        .filter(|name| {
+            lo.get_requires()
+                .iter()
+                .all(|r| r.allows(picked.iter().copied(), name))
+        })
+        .collect::<Vec<&str>>();
+    let is_restricted = lo.get_requires().iter().any(|r| {
+        picked
+            .iter()
+            .any(|(layer, name)| r.get_selector().matches(layer, name))
+    });
+    if is_restricted && (candidates.len() as u32) < lo.get_pick_min() {
+        return true;
+    }
+
+    let min = lo.get_pick_min().min(candidates.len() as u32);
+    let max = lo.get_pick_max().min(candidates.len() as u32);
+    for amount in min..=max {
+        let mut complete = true;
+
+        for_each_combination(candidates.len(), amount as usize, &mut |indices| {
+            if !complete {
+                return;
+            }
+
+            let picked_len = picked.len();
+            for index in indices {
+                let name = candidates[*index];
+                let is_excluded = picked.iter().any(|(other_layer, other_name)| {
+                    edition_config.is_excluded(lo.get_name(), name, other_layer, other_name)
+                });
+                if is_excluded {
+                    picked.truncate(picked_len);
+                    return;
+                }
+
+                picked.push((lo.get_name(), name));
+            }
+
+            complete = count_combinations(layers, edition_config, rest, picked, counter);
+            picked.truncate(picked_len);
+        });
+
+        if !complete {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn for_each_combination<F: FnMut(&[usize])>(n: usize, k: usize, f: &mut F) {
+    fn walk<F: FnMut(&[usize])>(start: usize, n: usize, k: usize, c: &mut Vec<usize>, f: &mut F) {
+        if c.len() == k {
+            f(c);
+            return;
+        }
+
+        for i in start..n {
+            c.push(i);
+            walk(i + 1, n, k, c, f);
+            c.pop();
+        }
+    }
+
+    walk(0, n, k, &mut Vec::with_capacity(k), f)
+}
+
+fn is_rule_involved(
+    edition_config: &EditionConfiguration,
+    order: &[LayerOrderConfiguration],
+    lo: &LayerOrderConfiguration,
+) -> bool {
+    if !lo.get_requires().is_empty() || !lo.get_only_if().is_empty() {
+        return true;
+    }
+
+    let is_referenced = order.iter().any(|o| {
+        o.get_only_if()
+            .iter()
+            .chain(o.get_requires().iter().map(|r| r.get_selector()))
+            .any(|s| s.get_layer() == lo.get_name())
+    });
+
+    is_referenced || edition_config.has_exclusions(lo.get_name())
+}
+
+/// Number of distinct file sets when picking between `min` and `max` of `n` files.
+fn pick_combinations(n: u32, min: u32, max: u32) -> u128 {
+    let min = min.min(n);
+    let max = max.min(n);
+
+    (min..=max)
+        .map(|k| binomial(n as u128, k as u128))
+        .fold(0u128, |sum, c| sum.saturating_add(c))
+        .max(1)
+}
+
+/// Binomial coefficient, saturating at `u128::MAX` once an intermediate product overflows.
+fn binomial(n: u128, k: u128) -> u128 {
+    if k > n {
+        return 0;
+    }
+
+    let k = k.min(n - k);
+    (0..k)
+        .try_fold(1u128, |result, i| {
+            result.checked_mul(n - i).map(|product| product / (i + 1))
+        })
+        .unwrap_or(u128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binomial_coefficients() {
+        assert_eq!(binomial(5, 0), 1);
+        assert_eq!(binomial(5, 2), 10);
+        assert_eq!(binomial(5, 5), 1);
+        assert_eq!(binomial(3, 4), 0);
+        assert_eq!(binomial(52, 5), 2_598_960);
+        assert_eq!(binomial(300, 150), u128::MAX);
+        assert_eq!(binomial(300, 299), 300);
+    }
+
+    #[test]
+    fn pick_combinations_of_multi_picks() {
+        assert_eq!(pick_combinations(4, 1, 1), 4);
+        assert_eq!(pick_combinations(4, 1, 2), 10);
+        assert_eq!(pick_combinations(4, 0, 4), 16);
+        assert_eq!(pick_combinations(2, 1, 3), 3);
+    }
+
+    #[test]
+    fn combinations_are_enumerated_in_order() {
+        let mut combinations = vec![];
+        for_each_combination(4, 2, &mut |c| combinations.push(c.to_vec()));
+
+        assert_eq!(
+            combinations,
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3]
+            ]
+        );
+    }
+}
@@ -1,33 +1,76 @@
 use std::path::Path;
 
 use clap::ArgMatches;
+use image::imageops::FilterType;
 
+use crate::config::output::{AnimationConfiguration, OutputFormat};
 use crate::logger::log_warn;
 
 #[derive(Debug)]
 pub struct AppConfiguration<L: AsRef<Path>, D: AsRef<Path>> {
     layers_dir: L,
     destination_dir: D,
-    size: u32,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+    extra_sizes: Vec<(u32, u32)>,
+    format: OutputFormat,
+    animation: AnimationConfiguration,
     max_tries: u32,
     cleanup: bool,
+    resume: bool,
+    ipfs: bool,
+    seed: Option<String>,
+    starting_index_seed: Option<String>,
+    pinned: Option<String>,
+    jobs: usize,
+    cache_size: usize,
 }
 
 impl AppConfiguration<&str, &str> {
     pub fn from_arg_matches(matches: &ArgMatches) -> AppConfiguration<&str, &str> {
         let layers_dir = matches.value_of("layer").unwrap();
         let destination_dir = matches.value_of("destination").unwrap();
-        let size = match matches.value_of("size").unwrap().parse::<u32>() {
-            Ok(size) => size,
-            Err(err) => {
+        let size = parse_dimension(matches.value_of("size").unwrap()).unwrap_or_else(|| {
+            log_warn("Invalid 'size' argument provided, use default of 1024.");
+
+            1024
+        });
+        let dimension = |name: &str| match matches.value_of(name).map(parse_dimension) {
+            Some(Some(dimension)) => dimension,
+            Some(None) => {
                 log_warn(format!(
-                    "Invalid 'size' argument provided, use default of 1024 ({}).",
-                    err
+                    "Invalid '{}' argument provided, use size of {}.",
+                    name, size
                 ));
 
-                512
+                size
             }
+            None => size,
         };
+        let width = dimension("width");
+        let height = dimension("height");
+        let filter = parse_filter(matches.value_of("filter").unwrap()).unwrap_or_else(|| {
+            log_warn("Invalid 'filter' argument provided, use default of lanczos3.");
+
+            FilterType::Lanczos3
+        });
+        let extra_sizes = matches
+            .values_of("extra-size")
+            .into_iter()
+            .flatten()
+            .filter_map(|value| {
+                let extra_size = parse_dimensions(value);
+                if extra_size.is_none() {
+                    log_warn(format!(
+                        "Invalid 'extra-size' argument ({}) provided, expected WIDTHxHEIGHT.",
+                        value
+                    ));
+                }
+
+                extra_size
+            })
+            .collect();
         let max_tries = match matches.value_of("max-retry").unwrap().parse::<u32>() {
             Ok(max_tries) => max_tries,
             Err(err) => {
@@ -39,13 +82,49 @@ impl AppConfiguration<&str, &str> {
                 1000
             }
         };
+        let jobs = match matches.value_of("jobs").unwrap().parse::<usize>() {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                log_warn(format!(
+                    "Invalid 'jobs' argument provided, use default of 0 ({}).",
+                    err
+                ));
+
+                0
+            }
+        };
+        let cache_size = match matches.value_of("cache-size").unwrap().parse::<usize>() {
+            Ok(cache_size) => cache_size,
+            Err(err) => {
+                log_warn(format!(
+                    "Invalid 'cache-size' argument provided, use default of 512 ({}).",
+                    err
+                ));
+
+                512
+            }
+        };
 
         AppConfiguration {
             layers_dir,
             destination_dir,
-            size,
+            width,
+            height,
+            filter,
+            extra_sizes,
+            format: OutputFormat::from_arg_matches(matches),
+            animation: AnimationConfiguration::from_arg_matches(matches),
             max_tries,
             cleanup: matches.is_present("cleanup"),
+            resume: matches.is_present("resume"),
+            ipfs: matches.is_present("ipfs"),
+            seed: matches.value_of("seed").map(|s| s.to_string()),
+            starting_index_seed: matches
+                .value_of("starting-index-seed")
+                .map(|s| s.to_string()),
+            pinned: matches.value_of("pinned").map(|s| s.to_string()),
+            jobs,
+            cache_size,
         }
     }
 }
@@ -59,8 +138,26 @@ impl<L: AsRef<Path>, D: AsRef<Path>> AppConfiguration<L, D> {
         self.destination_dir.as_ref()
     }
 
-    pub fn get_size(&self) -> u32 {
-        self.size
+    pub fn get_dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Filter used to resample layer images to the image size.
+    pub fn get_filter(&self) -> FilterType {
+        self.filter
+    }
+
+    /// Additional (e.g. thumbnail) sizes every image is saved in.
+    pub fn get_extra_sizes(&self) -> &Vec<(u32, u32)> {
+        &self.extra_sizes
+    }
+
+    pub fn get_format(&self) -> &OutputFormat {
+        &self.format
+    }
+
+    pub fn get_animation(&self) -> &AnimationConfiguration {
+        &self.animation
     }
 
     pub fn get_max_tries(&self) -> u32 {
@@ -70,4 +167,92 @@ impl<L: AsRef<Path>, D: AsRef<Path>> AppConfiguration<L, D> {
     pub fn is_cleanup_enabled(&self) -> bool {
         self.cleanup
     }
+
+    pub fn is_resume_enabled(&self) -> bool {
+        self.resume
+    }
+
+    /// Whether the images are packed for IPFS and the metadata links their CIDs.
+    pub fn is_ipfs_enabled(&self) -> bool {
+        self.ipfs
+    }
+
+    pub fn get_seed(&self) -> Option<&str> {
+        self.seed.as_deref()
+    }
+
+    /// Seed (e.g. a block hash) the starting index offset of the provenance record is derived from.
+    pub fn get_starting_index_seed(&self) -> Option<&str> {
+        self.starting_index_seed.as_deref()
+    }
+
+    /// CSV or JSON file of the editions with pinned traits.
+    pub fn get_pinned_file(&self) -> Option<&Path> {
+        self.pinned.as_deref().map(Path::new)
+    }
+
+    pub fn get_jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Memory cap (in MB) of the decoded layer image cache.
+    pub fn get_cache_size(&self) -> usize {
+        self.cache_size
+    }
+}
+
+fn parse_filter(value: &str) -> Option<FilterType> {
+    match value {
+        "nearest" => Some(FilterType::Nearest),
+        "triangle" => Some(FilterType::Triangle),
+        "catmull-rom" => Some(FilterType::CatmullRom),
+        "gaussian" => Some(FilterType::Gaussian),
+        "lanczos3" => Some(FilterType::Lanczos3),
+        _ => None,
+    }
+}
+
+/// Parses `WIDTHxHEIGHT`, or a single number for square dimensions.
+fn parse_dimensions(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x').unwrap_or((value, value));
+
+    Some((parse_dimension(width)?, parse_dimension(height)?))
+}
+
+/// Parses a width or height, which must not be 0.
+fn parse_dimension(value: &str) -> Option<u32> {
+    value
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|dimension| *dimension > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::arg_matches;
+
+    #[test]
+    fn parse_extra_sizes() {
+        assert_eq!(parse_dimensions("256x128"), Some((256, 128)));
+        assert_eq!(parse_dimensions("64"), Some((64, 64)));
+        assert_eq!(parse_dimensions("0x64"), None);
+        assert_eq!(parse_dimensions("64x"), None);
+    }
+
+    #[test]
+    fn zero_dimensions_fall_back() {
+        let matches = arg_matches(&["-s", "0", "--width", "0", "--height", "64"]);
+        assert_eq!(
+            AppConfiguration::from_arg_matches(&matches).get_dimensions(),
+            (1024, 64)
+        );
+
+        let matches = arg_matches(&["-s", "32", "--width", "0"]);
+        assert_eq!(
+            AppConfiguration::from_arg_matches(&matches).get_dimensions(),
+            (32, 32)
+        );
+    }
 }
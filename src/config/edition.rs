@@ -1,16 +1,36 @@
+use std::collections::BTreeMap;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
 
+use crate::config::metadata::{
+    AttributeConfiguration, CollectionConfiguration, MetadataConfiguration,
+};
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EditionConfiguration {
     _name: String,
     _description: String,
     _ipfs_uri: String,
+    #[serde(default)]
+    animation_uri: Option<String>,
     layers: Vec<LayerConfiguration>,
+    #[serde(default, alias = "incompatibilities")]
+    exclusions: Vec<ExclusionConfiguration>,
+    #[serde(default)]
+    metadata: MetadataConfiguration,
+    #[serde(default)]
+    collection: CollectionConfiguration,
+    #[serde(default)]
+    shuffle_editions: bool,
+    #[serde(default)]
+    one_of_ones: Vec<OneOfOneConfiguration>,
+    /// Exact number of editions per layer and file, e.g. `{ "Outline": { "Gold": 50 } }`.
+    #[serde(default)]
+    quotas: BTreeMap<String, BTreeMap<String, u32>>,
 }
 
 impl EditionConfiguration {
@@ -40,9 +60,54 @@ impl EditionConfiguration {
     pub fn _get_ipfs_uri(&self) -> &str {
         &self._ipfs_uri
     }
+    /// Base URI of the animations, defaults to the IPFS URI.
+    pub fn get_animation_uri(&self) -> &str {
+        self.animation_uri.as_deref().unwrap_or(&self._ipfs_uri)
+    }
     pub fn get_layers(&self) -> &Vec<LayerConfiguration> {
         &self.layers
     }
+    pub fn get_metadata(&self) -> &MetadataConfiguration {
+        &self.metadata
+    }
+    pub fn get_collection(&self) -> &CollectionConfiguration {
+        &self.collection
+    }
+    /// Whether the edition numbers are shuffled across all layer configurations.
+    pub fn is_shuffle_editions_enabled(&self) -> bool {
+        self.shuffle_editions
+    }
+    pub fn get_one_of_ones(&self) -> &Vec<OneOfOneConfiguration> {
+        &self.one_of_ones
+    }
+    pub fn get_quotas(&self) -> &BTreeMap<String, BTreeMap<String, u32>> {
+        &self.quotas
+    }
+    /// Number of all editions, the generated ones of the layer configurations and the one of ones.
+    pub fn get_edition_size(&self) -> u32 {
+        self.layers.iter().map(|l| l.get_size()).sum::<u32>() + self.one_of_ones.len() as u32
+    }
+
+    /// Whether any exclusion rule references the layer.
+    pub fn has_exclusions(&self, layer: &str) -> bool {
+        self.exclusions
+            .iter()
+            .any(|e| e.selector.layer == layer || e.excludes.iter().any(|s| s.layer == layer))
+    }
+
+    /// Whether the two traits must never appear together in the same edition.
+    pub fn is_excluded(
+        &self,
+        layer: &str,
+        name: &str,
+        other_layer: &str,
+        other_name: &str,
+    ) -> bool {
+        self.exclusions.iter().any(|e| {
+            (e.matches(layer, name) && e.excludes(other_layer, other_name))
+                || (e.matches(other_layer, other_name) && e.excludes(layer, name))
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,6 +115,14 @@ impl EditionConfiguration {
 pub struct LayerConfiguration {
     size: u32,
     order: Vec<LayerOrderConfiguration>,
+    /// Name prefix of the editions, instead of the collection name.
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    /// Static attributes added to the attributes of the layer files.
+    #[serde(default)]
+    attributes: Vec<AttributeConfiguration>,
 }
 
 impl LayerConfiguration {
@@ -60,6 +133,56 @@ impl LayerConfiguration {
     pub fn get_order(&self) -> &Vec<LayerOrderConfiguration> {
         &self.order
     }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn get_attributes(&self) -> &Vec<AttributeConfiguration> {
+        &self.attributes
+    }
+}
+
+/// Pre-rendered edition, placed at the given or a random edition number.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OneOfOneConfiguration {
+    image: PathBuf,
+    #[serde(default)]
+    edition: Option<u32>,
+    /// Name of the edition, instead of the collection name and edition number.
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    attributes: Vec<AttributeConfiguration>,
+}
+
+impl OneOfOneConfiguration {
+    pub fn get_image(&self) -> &Path {
+        &self.image
+    }
+
+    pub fn get_edition(&self) -> Option<u32> {
+        self.edition
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn get_attributes(&self) -> &Vec<AttributeConfiguration> {
+        &self.attributes
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,6 +193,16 @@ pub struct LayerOrderConfiguration {
     pick_min: u32,
     #[serde(default = "default_pick")]
     pick_max: u32,
+    #[serde(default)]
+    requires: Vec<RequirementConfiguration>,
+    #[serde(default)]
+    only_if: Vec<TraitSelector>,
+    #[serde(default)]
+    blend: BlendMode,
+    #[serde(default = "default_opacity")]
+    opacity: f32,
+    #[serde(default)]
+    transform: Option<TransformConfiguration>,
 }
 
 impl LayerOrderConfiguration {
@@ -84,8 +217,266 @@ impl LayerOrderConfiguration {
     pub fn get_pick_max(&self) -> u32 {
         self.pick_max
     }
+
+    pub fn get_requires(&self) -> &Vec<RequirementConfiguration> {
+        &self.requires
+    }
+
+    /// Traits of previously drawn layers, of which at least one must be present to draw this layer.
+    pub fn get_only_if(&self) -> &Vec<TraitSelector> {
+        &self.only_if
+    }
+
+    pub fn get_blend(&self) -> BlendMode {
+        self.blend
+    }
+
+    /// Opacity of the layer, clamped to 0.0 (transparent) - 1.0 (opaque).
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity.clamp(0.0, 1.0)
+    }
+
+    /// Transform of all files of the layer, unless a file has its own sidecar transform.
+    pub fn get_transform(&self) -> Option<&TransformConfiguration> {
+        self.transform.as_ref()
+    }
+}
+
+/// Places a layer file on the canvas. The `anchor` point of the (scaled, flipped and rotated)
+/// layer file is aligned with the same point of the canvas and then moved by `x` and `y`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformConfiguration {
+    #[serde(default)]
+    x: i64,
+    #[serde(default)]
+    y: i64,
+    #[serde(default)]
+    anchor: Anchor,
+    #[serde(default)]
+    scale: Option<f32>,
+    /// Clockwise rotation in degrees.
+    #[serde(default)]
+    rotation: Option<f32>,
+    #[serde(default)]
+    flip: Option<Flip>,
+}
+
+impl TransformConfiguration {
+    pub fn try_from_path<P: AsRef<Path>>(path: P) -> Result<TransformConfiguration> {
+        let context = format!(
+            "Try to create TransformConfiguration from path: {}",
+            path.as_ref().display()
+        );
+
+        let content = read_to_string(path).context(context.clone())?;
+        let config: TransformConfiguration = serde_json::from_str(&content).context(context)?;
+
+        Ok(config)
+    }
+
+    pub fn get_x(&self) -> i64 {
+        self.x
+    }
+
+    pub fn get_y(&self) -> i64 {
+        self.y
+    }
+
+    pub fn get_anchor(&self) -> Anchor {
+        self.anchor
+    }
+
+    pub fn get_scale(&self) -> f32 {
+        self.scale.unwrap_or(1.0)
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation.unwrap_or(0.0).rem_euclid(360.0)
+    }
+
+    pub fn get_flip(&self) -> Option<Flip> {
+        self.flip
+    }
+
+    /// Whether the layer file itself needs to be changed (not only placed) by the transform.
+    pub fn is_resampled(&self) -> bool {
+        self.get_scale() != 1.0 || self.get_rotation() != 0.0 || self.flip.is_some()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Relative horizontal and vertical position of the anchor point (0.0 - 1.0).
+    pub fn get_factors(&self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+/// Mode used to blend a layer onto the layers below it.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    #[serde(alias = "soft-light")]
+    SoftLight,
+    #[serde(alias = "additive")]
+    Add,
+}
+
+/// Restricts the files of a layer once a matching trait was drawn by a previous layer,
+/// either to an explicit list of `files` or to the files named like the matching trait.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequirementConfiguration {
+    #[serde(flatten)]
+    selector: TraitSelector,
+    #[serde(default)]
+    files: Option<Vec<String>>,
+    #[serde(default)]
+    same_file: bool,
+}
+
+impl RequirementConfiguration {
+    pub fn get_selector(&self) -> &TraitSelector {
+        &self.selector
+    }
+
+    pub fn get_files(&self) -> Option<&Vec<String>> {
+        self.files.as_ref()
+    }
+
+    pub fn is_same_file(&self) -> bool {
+        self.same_file
+    }
+
+    /// Whether a file of the layer named `name` may be drawn next to the `picked` traits
+    /// (given as layer and file name pairs).
+    pub fn allows<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(
+        &self,
+        picked: I,
+        name: &str,
+    ) -> bool {
+        let matched = picked
+            .into_iter()
+            .filter(|(layer, file)| self.selector.matches(layer, file))
+            .map(|(_, file)| file)
+            .collect::<Vec<&str>>();
+
+        if matched.is_empty() {
+            return true;
+        }
+
+        self.files
+            .as_ref()
+            .is_none_or(|files| files.iter().any(|f| f == name))
+            && (!self.same_file || matched.contains(&name))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExclusionConfiguration {
+    #[serde(flatten)]
+    selector: TraitSelector,
+    excludes: Vec<TraitSelector>,
+}
+
+impl ExclusionConfiguration {
+    fn matches(&self, layer: &str, name: &str) -> bool {
+        self.selector.matches(layer, name)
+    }
+
+    fn excludes(&self, layer: &str, name: &str) -> bool {
+        self.excludes.iter().any(|s| s.matches(layer, name))
+    }
+}
+
+/// Selects a single file of a layer by its name (without rarity weight) or,
+/// if no file is given, any file of the layer.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraitSelector {
+    layer: String,
+    #[serde(default)]
+    file: Option<String>,
+}
+
+impl TraitSelector {
+    pub fn get_layer(&self) -> &str {
+        &self.layer
+    }
+
+    pub fn get_file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn matches(&self, layer: &str, name: &str) -> bool {
+        self.layer == layer && self.file.as_ref().is_none_or(|f| f == name)
+    }
 }
 
 fn default_pick() -> u32 {
     1
 }
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::test_utils::test_edition_config;
+
+    #[test]
+    fn exclusions_match_both_directions() {
+        let config = test_edition_config(json!({
+            "exclusions": [
+                { "layer": "Hat", "file": "Cap", "excludes": [{ "layer": "Hood" }] }
+            ]
+        }));
+
+        assert!(config.is_excluded("Hat", "Cap", "Hood", "Red"));
+        assert!(config.is_excluded("Hood", "Blue", "Hat", "Cap"));
+        assert!(!config.is_excluded("Hat", "Crown", "Hood", "Red"));
+        assert!(!config.is_excluded("Hat", "Cap", "Eyes", "Red"));
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Maximum length (in bytes) of CIP-25 asset names.
+pub const CIP25_MAX_ASSET_NAME_LENGTH: usize = 32;
+
+/// Metadata standard and its profile-specific fields.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataConfiguration {
+    #[serde(default)]
+    profile: MetadataProfile,
+    /// Link to the edition on an external site, `{edition}` is replaced by the edition number.
+    external_url: Option<String>,
+    /// Background color (hex, without `#`) shown behind the image by marketplaces.
+    background_color: Option<String>,
+    /// Display types of layers whose file names are numeric values, e.g. `"Level": "number"`.
+    #[serde(default)]
+    display_types: HashMap<String, DisplayType>,
+    symbol: Option<String>,
+    seller_fee_basis_points: Option<u16>,
+    #[serde(default)]
+    creators: Vec<CreatorConfiguration>,
+    policy_id: Option<String>,
+}
+
+impl MetadataConfiguration {
+    pub fn get_profile(&self) -> MetadataProfile {
+        self.profile
+    }
+
+    pub fn get_external_url(&self, edition: u32) -> Option<String> {
+        self.external_url
+            .as_ref()
+            .map(|url| url.replace("{edition}", &edition.to_string()))
+    }
+
+    pub fn get_background_color(&self) -> Option<&str> {
+        self.background_color
+            .as_deref()
+            .map(|color| color.trim_start_matches('#'))
+    }
+
+    pub fn get_display_type(&self, layer: &str) -> Option<DisplayType> {
+        self.display_types.get(layer).copied()
+    }
+
+    pub fn get_symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    pub fn get_seller_fee_basis_points(&self) -> Option<u16> {
+        self.seller_fee_basis_points
+    }
+
+    pub fn get_creators(&self) -> &Vec<CreatorConfiguration> {
+        &self.creators
+    }
+
+    pub fn get_policy_id(&self) -> Option<&str> {
+        self.policy_id.as_deref()
+    }
+}
+
+/// Contract-level metadata of the whole collection, name and description default to the ones of
+/// the edition configuration and the royalties to the `sellerFeeBasisPoints` of the metadata.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionConfiguration {
+    name: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    external_link: Option<String>,
+    seller_fee_basis_points: Option<u16>,
+    /// Address receiving the royalties.
+    fee_recipient: Option<String>,
+}
+
+impl CollectionConfiguration {
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn get_image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    pub fn get_external_link(&self) -> Option<&str> {
+        self.external_link.as_deref()
+    }
+
+    pub fn get_seller_fee_basis_points(&self) -> Option<u16> {
+        self.seller_fee_basis_points
+    }
+
+    pub fn get_fee_recipient(&self) -> Option<&str> {
+        self.fee_recipient.as_deref()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum MetadataProfile {
+    /// ERC-721 metadata as read by OpenSea, the default.
+    #[default]
+    #[serde(alias = "opensea")]
+    Erc721,
+    /// Solana Metaplex token metadata.
+    #[serde(alias = "solana")]
+    Metaplex,
+    /// Cardano CIP-25 metadata, the editions are placed in a `721` policy map.
+    #[serde(alias = "cardano")]
+    Cip25,
+}
+
+/// Static attribute added to the metadata of every edition of a layer configuration.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeConfiguration {
+    #[serde(alias = "trait_type")]
+    trait_type: String,
+    value: Value,
+    #[serde(default, alias = "display_type")]
+    display_type: Option<DisplayType>,
+}
+
+impl AttributeConfiguration {
+    pub fn get_trait_type(&self) -> &str {
+        &self.trait_type
+    }
+
+    pub fn get_value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Value like it appears in the rarity report, strings without quotes.
+    pub fn get_value_string(&self) -> String {
+        match &self.value {
+            Value::String(value) => value.to_string(),
+            value => value.to_string(),
+        }
+    }
+
+    pub fn get_display_type(&self) -> Option<DisplayType> {
+        self.display_type
+    }
+}
+
+/// How marketplaces display numeric trait values.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayType {
+    Number,
+    BoostNumber,
+    BoostPercentage,
+    Date,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct CreatorConfiguration {
+    address: String,
+    /// Share (in percent) of the royalties.
+    share: u8,
+}
+
+impl CreatorConfiguration {
+    pub fn get_share(&self) -> u8 {
+        self.share
+    }
+}
+
+/// Asset names must not contain whitespace or special characters, e.g. `Sdx Nft #1` -> `SdxNft1`.
+pub fn cip25_asset_name(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
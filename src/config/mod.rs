@@ -1,2 +1,6 @@
 pub mod app;
 pub mod edition;
+pub mod metadata;
+pub mod output;
+pub mod pinned;
+pub mod uri;
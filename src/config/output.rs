@@ -0,0 +1,216 @@
+use clap::ArgMatches;
+use image::Rgb;
+
+use crate::logger::log_warn;
+
+/// Encoding of the processed images.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    Png {
+        compression: PngCompression,
+        /// Quantizes the image to an indexed palette of this many colors.
+        palette: Option<usize>,
+    },
+    WebP {
+        quality: f32,
+        lossless: bool,
+    },
+    Jpeg {
+        quality: u8,
+        /// JPEG has no alpha channel, so transparent pixels are composited onto this color.
+        background: Rgb<u8>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+impl OutputFormat {
+    pub fn from_arg_matches(matches: &ArgMatches) -> OutputFormat {
+        let quality = match matches.value_of("quality").unwrap().parse::<u8>() {
+            Ok(quality) if quality <= 100 => quality,
+            _ => {
+                log_warn("Invalid 'quality' argument provided, use default of 90.");
+
+                90
+            }
+        };
+
+        match matches.value_of("format").unwrap() {
+            "webp" => OutputFormat::WebP {
+                quality: quality as f32,
+                lossless: matches.is_present("lossless"),
+            },
+            "jpeg" => OutputFormat::Jpeg {
+                quality,
+                background: parse_color(matches.value_of("background").unwrap()).unwrap_or_else(
+                    || {
+                        log_warn("Invalid 'background' argument provided, use default of #ffffff.");
+
+                        Rgb([255, 255, 255])
+                    },
+                ),
+            },
+            _ => {
+                let compression = match matches.value_of("png-compression").unwrap() {
+                    "fast" => PngCompression::Fast,
+                    "best" => PngCompression::Best,
+                    _ => PngCompression::Default,
+                };
+                let palette = matches
+                    .value_of("palette")
+                    .and_then(|p| match p.parse::<usize>() {
+                        Ok(colors) if (2..=256).contains(&colors) => Some(colors),
+                        _ => {
+                            log_warn(format!(
+                                "Invalid 'palette' argument ({}) provided, expected 2 to 256 colors, \
+                                the palette is not quantized.",
+                                p
+                            ));
+
+                            None
+                        }
+                    });
+
+                OutputFormat::Png {
+                    compression,
+                    palette,
+                }
+            }
+        }
+    }
+
+    /// File extension of the encoded images, without a leading dot.
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png { .. } => "png",
+            OutputFormat::WebP { .. } => "webp",
+            OutputFormat::Jpeg { .. } => "jpg",
+        }
+    }
+}
+
+/// Encoding and frame timing of editions with animated layers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationConfiguration {
+    format: AnimationFormat,
+    frame_delay: u32,
+    strategy: FrameStrategy,
+    max_frames: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    WebP { quality: f32, lossless: bool },
+}
+
+/// How the frames of animated layers with different frame counts or timings are combined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameStrategy {
+    /// Every layer loops with its own timing, until all layers end at the same time.
+    Timeline,
+    /// Shorter animations loop frame by frame with the frame delays of the longest one.
+    Longest,
+}
+
+impl AnimationConfiguration {
+    pub fn from_arg_matches(matches: &ArgMatches) -> AnimationConfiguration {
+        let format = match matches.value_of("animation-format").unwrap() {
+            "apng" => AnimationFormat::Apng,
+            "webp" => AnimationFormat::WebP {
+                quality: matches
+                    .value_of("quality")
+                    .and_then(|q| q.parse::<u8>().ok())
+                    .map_or(90.0, |q| q.min(100) as f32),
+                lossless: matches.is_present("lossless"),
+            },
+            _ => AnimationFormat::Gif,
+        };
+        let frame_delay = match matches.value_of("frame-delay").unwrap().parse::<u32>() {
+            Ok(frame_delay) if frame_delay > 0 => frame_delay,
+            _ => {
+                log_warn("Invalid 'frame-delay' argument provided, use default of 100.");
+
+                100
+            }
+        };
+        let strategy = match matches.value_of("frame-strategy").unwrap() {
+            "longest" => FrameStrategy::Longest,
+            _ => FrameStrategy::Timeline,
+        };
+        let max_frames = match matches.value_of("max-frames").unwrap().parse::<usize>() {
+            Ok(max_frames) if max_frames > 0 => max_frames,
+            _ => {
+                log_warn("Invalid 'max-frames' argument provided, use default of 500.");
+
+                500
+            }
+        };
+
+        AnimationConfiguration {
+            format,
+            frame_delay,
+            strategy,
+            max_frames,
+        }
+    }
+
+    pub fn get_format(&self) -> &AnimationFormat {
+        &self.format
+    }
+
+    /// Delay (in ms) of frames without an own delay, e.g. frames of a directory.
+    pub fn get_frame_delay(&self) -> u32 {
+        self.frame_delay
+    }
+
+    pub fn get_strategy(&self) -> FrameStrategy {
+        self.strategy
+    }
+
+    pub fn get_max_frames(&self) -> usize {
+        self.max_frames
+    }
+}
+
+impl AnimationFormat {
+    /// File extension of the encoded animations, without a leading dot.
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+            AnimationFormat::WebP { .. } => "webp",
+        }
+    }
+}
+
+/// Parses `#rrggbb` (the `#` is optional).
+fn parse_color(value: &str) -> Option<Rgb<u8>> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+
+    Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_colors() {
+        assert_eq!(parse_color("#ff8000"), Some(Rgb([255, 128, 0])));
+        assert_eq!(parse_color("00FF10"), Some(Rgb([0, 255, 16])));
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+    }
+}
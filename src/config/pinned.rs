@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+const CSV_EDITION_COLUMN: &str = "edition";
+const CSV_LAYER_CONFIGURATION_COLUMN: &str = "layerConfiguration";
+const CSV_DNA_COLUMN: &str = "dna";
+/// Separates multiple files of the same layer in a CSV cell.
+const CSV_FILE_DELIMITER: char = '|';
+
+/// Edition with explicitly selected traits, given as layer to file names or as the DNA of an
+/// edition of a previous run.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinnedEdition {
+    edition: u32,
+    /// Index of the layer configuration, the first one allowing the traits if none is given.
+    #[serde(default)]
+    layer_configuration: Option<usize>,
+    #[serde(default)]
+    dna: Option<String>,
+    #[serde(default)]
+    traits: HashMap<String, PinnedFiles>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PinnedFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl PinnedEdition {
+    pub fn get_edition(&self) -> u32 {
+        self.edition
+    }
+
+    pub fn get_layer_configuration(&self) -> Option<usize> {
+        self.layer_configuration
+    }
+
+    pub fn get_dna(&self) -> Option<&str> {
+        self.dna.as_deref()
+    }
+
+    /// Pinned file names by layer.
+    pub fn get_traits(&self) -> HashMap<&str, Vec<&str>> {
+        self.traits
+            .iter()
+            .map(|(layer, files)| {
+                let files = match files {
+                    PinnedFiles::One(file) => vec![file.as_str()],
+                    PinnedFiles::Many(files) => files.iter().map(|f| f.as_str()).collect(),
+                };
+
+                (layer.as_str(), files)
+            })
+            .collect()
+    }
+}
+
+/// Reads the pinned editions of a JSON array or a CSV file (by its extension). CSV files have a
+/// header with the `edition` column, an optional `layerConfiguration` and `dna` column and a
+/// column per layer, whose cells contain the file names separated by `|` (or nothing).
+pub fn read_pinned_editions<P: AsRef<Path>>(path: P) -> Result<Vec<PinnedEdition>> {
+    let context = format!(
+        "Read pinned editions from path: {}",
+        path.as_ref().display()
+    );
+
+    let content = read_to_string(&path).context(context.clone())?;
+    let is_csv = path
+        .as_ref()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+
+    if is_csv {
+        parse_csv(&content).context(context)
+    } else {
+        serde_json::from_str(&content).context(context)
+    }
+}
+
+fn parse_csv(content: &str) -> Result<Vec<PinnedEdition>> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header = match lines.next() {
+        Some((_, header)) => split_csv_line(header),
+        None => return Ok(vec![]),
+    };
+    if !header.contains(&CSV_EDITION_COLUMN) {
+        bail!("CSV header has no '{}' column", CSV_EDITION_COLUMN);
+    }
+
+    lines
+        .map(|(index, line)| {
+            let cells = split_csv_line(line);
+            if cells.len() != header.len() {
+                bail!(
+                    "Line {} has {} columns, expected {}",
+                    index + 1,
+                    cells.len(),
+                    header.len()
+                );
+            }
+
+            let mut pinned = PinnedEdition {
+                edition: 0,
+                layer_configuration: None,
+                dna: None,
+                traits: HashMap::new(),
+            };
+            for (column, cell) in header.iter().zip(cells) {
+                match *column {
+                    CSV_EDITION_COLUMN => {
+                        pinned.edition = cell
+                            .parse()
+                            .context(format!("Line {} has an invalid edition", index + 1))?
+                    }
+                    CSV_LAYER_CONFIGURATION_COLUMN if !cell.is_empty() => {
+                        pinned.layer_configuration = Some(cell.parse().context(format!(
+                            "Line {} has an invalid layer configuration",
+                            index + 1
+                        ))?)
+                    }
+                    CSV_DNA_COLUMN if !cell.is_empty() => pinned.dna = Some(cell.to_string()),
+                    CSV_LAYER_CONFIGURATION_COLUMN | CSV_DNA_COLUMN => {}
+                    layer if !cell.is_empty() => {
+                        let files = cell
+                            .split(CSV_FILE_DELIMITER)
+                            .map(|f| f.trim().to_string())
+                            .collect();
+                        pinned
+                            .traits
+                            .insert(layer.to_string(), PinnedFiles::Many(files));
+                    }
+                    _ => {}
+                }
+            }
+
+            Ok(pinned)
+        })
+        .collect()
+}
+
+fn split_csv_line(line: &str) -> Vec<&str> {
+    line.split(',').map(|cell| cell.trim()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_csv_with_traits_and_dna() {
+        let pinned = parse_csv(
+            "edition,dna,Background,Hat\n\
+            1,,Blue,Cap | Pin\n\
+            \n\
+            5,abc123,,\n",
+        )
+        .unwrap();
+
+        assert_eq!(pinned.len(), 2);
+        assert_eq!(pinned[0].get_edition(), 1);
+        assert_eq!(pinned[0].get_dna(), None);
+        assert_eq!(pinned[0].get_traits()["Background"], vec!["Blue"]);
+        assert_eq!(pinned[0].get_traits()["Hat"], vec!["Cap", "Pin"]);
+        assert_eq!(pinned[1].get_dna(), Some("abc123"));
+        assert!(pinned[1].get_traits().is_empty());
+        assert!(parse_csv("Background\nBlue").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use clap::ArgMatches;
+
+/// New URIs of the `update-uri` subcommand. A URI is either a base URI, which the file name
+/// (`{edition}.{extension}`) is appended to, or a template with the placeholders `{cid}`,
+/// `{edition}` and `{extension}`.
+#[derive(Debug)]
+pub struct UriConfiguration<'a> {
+    image_uri: &'a str,
+    animation_uri: Option<&'a str>,
+    cid: Option<&'a str>,
+    output_dir: Option<&'a str>,
+}
+
+impl UriConfiguration<'_> {
+    pub fn new<'a>(image_uri: &'a str, animation_uri: Option<&'a str>) -> UriConfiguration<'a> {
+        UriConfiguration {
+            image_uri,
+            animation_uri,
+            cid: None,
+            output_dir: None,
+        }
+    }
+
+    pub fn from_arg_matches(matches: &ArgMatches) -> UriConfiguration<'_> {
+        UriConfiguration {
+            image_uri: matches.value_of("image-uri").unwrap(),
+            animation_uri: matches.value_of("animation-uri"),
+            cid: matches.value_of("cid"),
+            output_dir: matches.value_of("output-dir"),
+        }
+    }
+
+    /// Directory the updated metadata is written to, instead of updating it in place.
+    pub fn get_output_dir(&self) -> Option<&str> {
+        self.output_dir
+    }
+
+    pub fn get_image_uri(&self, edition: u32, extension: &str) -> Result<String> {
+        render_uri(self.image_uri, self.cid, edition, extension)
+    }
+
+    pub fn has_animation_uri(&self) -> bool {
+        self.animation_uri.is_some()
+    }
+
+    /// New animation URI, if the animation URIs should be updated at all.
+    pub fn get_animation_uri(&self, edition: u32, extension: &str) -> Option<Result<String>> {
+        self.animation_uri
+            .map(|uri| render_uri(uri, self.cid, edition, extension))
+    }
+}
+
+fn render_uri(template: &str, cid: Option<&str>, edition: u32, extension: &str) -> Result<String> {
+    let uri = if template.contains("{edition}") {
+        template.to_string()
+    } else if template.ends_with('/') {
+        format!("{}{{edition}}.{{extension}}", template)
+    } else {
+        format!("{}/{{edition}}.{{extension}}", template)
+    };
+
+    if uri.contains("{cid}") && cid.is_none() {
+        bail!(
+            "URI ({}) contains {{cid}}, but no 'cid' is provided",
+            template
+        );
+    }
+
+    Ok(uri
+        .replace("{cid}", cid.unwrap_or_default())
+        .replace("{edition}", &edition.to_string())
+        .replace("{extension}", extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_base_uris_and_templates() {
+        assert_eq!(
+            render_uri("ipfs://abc", None, 7, "png").unwrap(),
+            "ipfs://abc/7.png"
+        );
+        assert_eq!(
+            render_uri("ipfs://abc/", None, 7, "png").unwrap(),
+            "ipfs://abc/7.png"
+        );
+        assert_eq!(
+            render_uri("ipfs://{cid}/{edition}.webp", Some("xyz"), 12, "png").unwrap(),
+            "ipfs://xyz/12.webp"
+        );
+        assert!(render_uri("ipfs://{cid}/", None, 1, "png").is_err());
+    }
+}
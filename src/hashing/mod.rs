@@ -1,8 +1,68 @@
 use sha2::{Digest, Sha256};
 
 pub fn simple_sha256(data: impl AsRef<[u8]>) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    format!("{:x}", result)
+    format!("{:x}", Sha256::digest(data))
+}
+
+pub fn sha256_digest(data: impl AsRef<[u8]>) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Hash of the concatenated (hex) hashes, in the given order.
+pub fn concatenated_sha256<S: AsRef<str>>(hashes: &[S]) -> String {
+    let concatenated = hashes.iter().map(|h| h.as_ref()).collect::<String>();
+
+    simple_sha256(concatenated)
+}
+
+/// Offset in `0..count` derived from the seed. A `0x` prefixed hex seed (e.g. a block hash) is
+/// used as number, any other seed is hashed first.
+pub fn starting_index(seed: &str, count: u32) -> u32 {
+    if count == 0 {
+        return 0;
+    }
+
+    let bytes = match seed.strip_prefix("0x").map(hex_bytes) {
+        Some(Some(bytes)) => bytes,
+        _ => sha256_digest(seed).to_vec(),
+    };
+
+    bytes.iter().fold(0u64, |rest, byte| {
+        ((rest << 8) | *byte as u64) % count as u64
+    }) as u32
+}
+
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.is_ascii() {
+        return None;
+    }
+
+    let hex = if hex.len() % 2 == 1 {
+        format!("0{}", hex)
+    } else {
+        hex.to_string()
+    };
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_index_of_block_hashes_and_seeds() {
+        assert_eq!(starting_index("0x0a", 7), 3);
+        assert_eq!(starting_index("0x100", 10), 6);
+        assert_eq!(
+            starting_index(&format!("0x{}", "f".repeat(64)), 10000),
+            // 2^256 - 1 mod 10000
+            9935
+        );
+        assert!(starting_index("my seed", 100) < 100);
+        assert_eq!(starting_index("0x0a", 0), 0);
+    }
 }
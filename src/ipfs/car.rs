@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::ipfs::cid::{write_varint, Cid};
+use crate::ipfs::unixfs::{BlockData, Dag};
+
+const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+const CARV2_HEADER_SIZE: u64 = 40;
+
+/// Writes all blocks of the DAG into a CARv2 file (without index) with the given root.
+pub fn write_car<P: AsRef<Path>>(path: P, root: &Cid, dag: &Dag) -> Result<()> {
+    let context = format!("Write CAR file ({})", path.as_ref().display());
+    let mut writer = BufWriter::new(File::create(&path).context(context.clone())?);
+
+    let header = carv1_header(root);
+    let blocks = dag
+        .get_blocks()
+        .iter()
+        .map(|(cid, data)| (cid.to_bytes(), data))
+        .collect::<Vec<(Vec<u8>, &BlockData)>>();
+    let data_size = header.len() as u64
+        + blocks
+            .iter()
+            .map(|(cid, data)| {
+                let length = (cid.len() + data.len()) as u64;
+                varint(length).len() as u64 + length
+            })
+            .sum::<u64>();
+
+    writer.write_all(&CARV2_PRAGMA).context(context.clone())?;
+    // characteristics, data offset, data size and index offset (no index)
+    writer.write_all(&[0; 16]).context(context.clone())?;
+    writer
+        .write_all(&(CARV2_PRAGMA.len() as u64 + CARV2_HEADER_SIZE).to_le_bytes())
+        .context(context.clone())?;
+    writer
+        .write_all(&data_size.to_le_bytes())
+        .context(context.clone())?;
+    writer
+        .write_all(&0u64.to_le_bytes())
+        .context(context.clone())?;
+
+    writer.write_all(&header).context(context.clone())?;
+    for (cid, data) in blocks {
+        writer
+            .write_all(&varint((cid.len() + data.len()) as u64))
+            .context(context.clone())?;
+        writer.write_all(&cid).context(context.clone())?;
+
+        match data {
+            BlockData::Node(data) => writer.write_all(data).context(context.clone())?,
+            BlockData::Chunk {
+                path,
+                offset,
+                length,
+            } => {
+                let mut chunk = vec![0; *length];
+                let mut file = File::open(path).context(context.clone())?;
+                file.seek(SeekFrom::Start(*offset))
+                    .context(context.clone())?;
+                file.read_exact(&mut chunk).context(context.clone())?;
+                writer.write_all(&chunk).context(context.clone())?;
+            }
+        }
+    }
+    writer.flush().context(context)?;
+
+    Ok(())
+}
+
+/// Length-prefixed DAG-CBOR header `{"roots": [root], "version": 1}`.
+fn carv1_header(root: &Cid) -> Vec<u8> {
+    let cid = root.to_bytes();
+    let mut cbor = vec![0xa2, 0x65];
+    cbor.extend_from_slice(b"roots");
+    cbor.push(0x81);
+    // tag 42 (CID), byte string with a leading 0x00 (identity multibase)
+    cbor.extend_from_slice(&[0xd8, 0x2a, 0x58, cid.len() as u8 + 1, 0x00]);
+    cbor.extend_from_slice(&cid);
+    cbor.push(0x67);
+    cbor.extend_from_slice(b"version");
+    cbor.push(0x01);
+
+    let mut header = varint(cbor.len() as u64);
+    header.extend_from_slice(&cbor);
+
+    header
+}
+
+fn varint(value: u64) -> Vec<u8> {
+    let mut buffer = vec![];
+    write_varint(&mut buffer, value);
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::fs::{create_dir_all, read, write};
+
+    use super::*;
+    use crate::ipfs::cid::Codec;
+    use crate::ipfs::unixfs::import_directory;
+    use crate::test_utils::TempDir;
+
+    fn read_varint(bytes: &[u8], position: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[*position];
+            *position += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    #[test]
+    fn car_blocks_round_trip() {
+        let dir = TempDir::new("car");
+        let root = dir.join("root");
+        create_dir_all(root.join("sub")).unwrap();
+        write(root.join("a.txt"), "hello\n").unwrap();
+        // larger than a chunk, so chunks are read back from the file at their offset
+        let content = (0..300 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        write(root.join("sub").join("big.bin"), &content).unwrap();
+
+        let mut dag = Dag::default();
+        let (directory, _) = import_directory(&mut dag, &root, &|_| true).unwrap();
+        let car_path = dir.join("root.car");
+        write_car(&car_path, directory.get_cid(), &dag).unwrap();
+        let car = read(&car_path).unwrap();
+
+        // CARv2 pragma and header, the CARv1 data follows without an index
+        assert_eq!(car[..11], CARV2_PRAGMA);
+        let field = |index: usize| {
+            let start = 11 + 16 + index * 8;
+            u64::from_le_bytes(car[start..start + 8].try_into().unwrap())
+        };
+        let data_offset = field(0) as usize;
+        assert_eq!(data_offset, 51);
+        assert_eq!(field(1) as usize, car.len() - data_offset);
+        assert_eq!(field(2), 0);
+
+        // DAG-CBOR {"roots": [root], "version": 1}, the root CID with tag 42
+        let mut header = vec![0x3a, 0xa2, 0x65];
+        header.extend_from_slice(b"roots");
+        header.extend_from_slice(&[0x81, 0xd8, 0x2a, 0x58, 0x25, 0x00]);
+        header.extend_from_slice(&directory.get_cid().to_bytes());
+        header.push(0x67);
+        header.extend_from_slice(b"version");
+        header.push(0x01);
+        assert_eq!(car[data_offset..data_offset + header.len()], header);
+        let mut position = data_offset + header.len();
+
+        let mut cids = HashSet::new();
+        while position < car.len() {
+            let length = read_varint(&car, &mut position) as usize;
+            let (cid, data) = car[position..position + length].split_at(36);
+            let codec = match cid[1] {
+                0x55 => Codec::Raw,
+                0x70 => Codec::DagPb,
+                codec => panic!("unexpected codec {:x}", codec),
+            };
+            // every block hashes to its CID
+            assert_eq!(Cid::new(codec, data).to_bytes(), cid);
+            cids.insert(Cid::new(codec, data));
+            position += length;
+        }
+
+        assert!(cids.contains(directory.get_cid()));
+        assert_eq!(
+            cids,
+            dag.get_blocks()
+                .iter()
+                .map(|(cid, _)| *cid)
+                .collect::<HashSet<Cid>>()
+        );
+    }
+}
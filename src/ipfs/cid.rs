@@ -0,0 +1,95 @@
+use std::fmt::{Display, Formatter};
+
+use crate::hashing::sha256_digest;
+
+const CID_VERSION: u64 = 1;
+const SHA2_256: u64 = 0x12;
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Multicodec of the content a CID points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    Raw = 0x55,
+    DagPb = 0x70,
+}
+
+/// Content identifier (CIDv1 with a sha2-256 multihash).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cid {
+    codec: Codec,
+    digest: [u8; 32],
+}
+
+impl Cid {
+    pub fn new(codec: Codec, data: &[u8]) -> Cid {
+        Cid {
+            codec,
+            digest: sha256_digest(data),
+        }
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_varint(&mut bytes, CID_VERSION);
+        write_varint(&mut bytes, self.codec as u64);
+        write_varint(&mut bytes, SHA2_256);
+        write_varint(&mut bytes, self.digest.len() as u64);
+        bytes.extend_from_slice(&self.digest);
+
+        bytes
+    }
+}
+
+/// Multibase encoded CID (lowercase base32, prefixed with `b`).
+impl Display for Cid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "b{}", base32(&self.to_bytes()))
+    }
+}
+
+/// Unsigned LEB128 varint, as used by multiformats and protobuf.
+pub fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// RFC 4648 base32 without padding.
+fn base32(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_cids_match_reference() {
+        assert_eq!(
+            Cid::new(Codec::Raw, b"").to_string(),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert_eq!(
+            Cid::new(Codec::Raw, b"hello world").to_string(),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::ipfs::car::write_car;
+use crate::ipfs::unixfs::{import_directory, Dag};
+
+pub use cid::Cid;
+
+mod car;
+mod cid;
+mod unixfs;
+
+/// Directory added to IPFS, with the CIDs of its files (relative path and CID).
+#[derive(Debug)]
+pub struct IpfsDirectory {
+    cid: Cid,
+    files: Vec<(String, Cid)>,
+}
+
+impl IpfsDirectory {
+    pub fn get_cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    pub fn get_files(&self) -> &Vec<(String, Cid)> {
+        &self.files
+    }
+}
+
+/// Computes the CIDs of the directory and its files offline, like
+/// `ipfs add -r --cid-version 1`, and packages all blocks into a CARv2 file ready to be imported
+/// by any IPFS node or pinning service. Only the included files and directories are packed.
+pub fn pack_directory<P: AsRef<Path>, C: AsRef<Path>>(
+    path: P,
+    car_path: C,
+    is_included: &dyn Fn(&Path) -> bool,
+) -> Result<IpfsDirectory> {
+    let context = format!("Pack directory ({}) for IPFS", path.as_ref().display());
+
+    let mut dag = Dag::default();
+    let (directory, files) =
+        import_directory(&mut dag, &path, is_included).context(context.clone())?;
+    write_car(car_path, directory.get_cid(), &dag).context(context)?;
+
+    Ok(IpfsDirectory {
+        cid: *directory.get_cid(),
+        files: files
+            .iter()
+            .map(|f| (f.get_name().to_string(), *f.get_cid()))
+            .collect(),
+    })
+}
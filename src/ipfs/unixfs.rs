@@ -0,0 +1,457 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{read_dir, File};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::ipfs::cid::{write_varint, Cid, Codec};
+
+/// Chunk size of the reference implementation (`size-262144`).
+const CHUNK_SIZE: usize = 256 * 1024;
+/// Maximum number of links of a file node in the balanced layout.
+const MAX_LINKS: usize = 174;
+/// Directories whose links (name and CID) exceed this size are sharded into a HAMT.
+const HAMT_SHARDING_SIZE: usize = 256 * 1024;
+const HAMT_FANOUT: u64 = 256;
+const HAMT_HASH_MURMUR3: u64 = 0x22;
+
+const UNIXFS_DIRECTORY: u64 = 1;
+const UNIXFS_FILE: u64 = 2;
+const UNIXFS_HAMT_SHARD: u64 = 5;
+
+/// Content of a block, file chunks are read again from their file when needed.
+#[derive(Debug)]
+pub enum BlockData {
+    Chunk {
+        path: PathBuf,
+        offset: u64,
+        length: usize,
+    },
+    Node(Vec<u8>),
+}
+
+impl BlockData {
+    pub fn len(&self) -> usize {
+        match self {
+            BlockData::Chunk { length, .. } => *length,
+            BlockData::Node(data) => data.len(),
+        }
+    }
+}
+
+/// Blocks of an imported DAG, each block is only stored once.
+#[derive(Debug, Default)]
+pub struct Dag {
+    blocks: Vec<(Cid, BlockData)>,
+    cids: HashSet<Cid>,
+}
+
+impl Dag {
+    pub fn get_blocks(&self) -> &Vec<(Cid, BlockData)> {
+        &self.blocks
+    }
+
+    fn add(&mut self, cid: Cid, data: BlockData) {
+        if self.cids.insert(cid) {
+            self.blocks.push((cid, data));
+        }
+    }
+
+    fn add_node(&mut self, data: Vec<u8>) -> Cid {
+        let cid = Cid::new(Codec::DagPb, &data);
+        self.add(cid, BlockData::Node(data));
+
+        cid
+    }
+}
+
+/// Link to an imported file or directory, the size is the cumulative size of all its blocks.
+#[derive(Debug, Clone)]
+pub struct Link {
+    name: String,
+    cid: Cid,
+    size: u64,
+}
+
+impl Link {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_cid(&self) -> &Cid {
+        &self.cid
+    }
+}
+
+/// Imports a directory (UnixFS, CIDv1 with raw leaves) like `ipfs add -r --cid-version 1`, returns
+/// the link to the directory and the links to all of its files. Entries which aren't included are
+/// left out.
+pub fn import_directory<P: AsRef<Path>>(
+    dag: &mut Dag,
+    path: P,
+    is_included: &dyn Fn(&Path) -> bool,
+) -> Result<(Link, Vec<Link>)> {
+    let path = path.as_ref();
+    let mut entries = read_dir(path)
+        .context(format!("Read directory ({})", path.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| is_included(p))
+        .collect::<Vec<PathBuf>>();
+    entries.sort();
+
+    let mut links = vec![];
+    let mut files = vec![];
+    for entry in entries {
+        let name = entry
+            .file_name()
+            .and_then(|n| n.to_str())
+            .context(format!("Invalid file name ({})", entry.display()))?
+            .to_string();
+
+        if entry.is_dir() {
+            let (link, mut dir_files) = import_directory(dag, &entry, is_included)?;
+            files.extend(dir_files.drain(..).map(|f| Link {
+                name: format!("{}/{}", name, f.name),
+                ..f
+            }));
+            links.push(Link { name, ..link });
+        } else {
+            let link = Link {
+                name,
+                ..import_file(dag, &entry)?
+            };
+            files.push(link.clone());
+            links.push(link);
+        }
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let estimated_size = links
+        .iter()
+        .map(|l| l.name.len() + l.cid.to_bytes().len())
+        .sum::<usize>();
+    let link = if estimated_size >= HAMT_SHARDING_SIZE {
+        hamt_directory(dag, &links)?
+    } else {
+        basic_directory(dag, &links)
+    };
+
+    Ok((Link { name, ..link }, files))
+}
+
+/// Imports a file, split into chunks which are combined in a balanced layout.
+fn import_file(dag: &mut Dag, path: &Path) -> Result<Link> {
+    let context = format!("Import file ({})", path.display());
+    let mut file = File::open(path).context(context.clone())?;
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut offset = 0u64;
+    // links to the chunks with their file size
+    let mut level = vec![];
+
+    loop {
+        let length = read_chunk(&mut file, &mut chunk).context(context.clone())?;
+        if length == 0 && !level.is_empty() {
+            break;
+        }
+
+        let cid = Cid::new(Codec::Raw, &chunk[..length]);
+        dag.add(
+            cid,
+            BlockData::Chunk {
+                path: path.to_path_buf(),
+                offset,
+                length,
+            },
+        );
+        level.push((link("", cid, length as u64), length as u64));
+        offset += length as u64;
+
+        if length < CHUNK_SIZE {
+            break;
+        }
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(MAX_LINKS)
+            .map(|children| {
+                let file_size = children.iter().map(|(_, size)| size).sum::<u64>();
+                let mut unixfs = vec![];
+                write_varint_field(&mut unixfs, 1, UNIXFS_FILE);
+                write_varint_field(&mut unixfs, 3, file_size);
+                for (_, size) in children {
+                    write_varint_field(&mut unixfs, 4, *size);
+                }
+
+                let links = children
+                    .iter()
+                    .map(|(link, _)| link.clone())
+                    .collect::<Vec<Link>>();
+
+                (add_node(dag, &links, &unixfs), file_size)
+            })
+            .collect();
+    }
+
+    Ok(level.remove(0).0)
+}
+
+fn read_chunk(file: &mut File, chunk: &mut [u8]) -> std::io::Result<usize> {
+    let mut length = 0;
+    while length < chunk.len() {
+        match file.read(&mut chunk[length..])? {
+            0 => break,
+            read => length += read,
+        }
+    }
+
+    Ok(length)
+}
+
+fn basic_directory(dag: &mut Dag, links: &[Link]) -> Link {
+    let mut unixfs = vec![];
+    write_varint_field(&mut unixfs, 1, UNIXFS_DIRECTORY);
+
+    let mut links = links.to_vec();
+    links.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+
+    add_node(dag, &links, &unixfs)
+}
+
+/// Entry of a HAMT shard, either a directory entry or a nested shard.
+enum HamtEntry {
+    Link(Link),
+    Shard(HamtShard),
+}
+
+#[derive(Default)]
+struct HamtShard {
+    entries: BTreeMap<u8, HamtEntry>,
+}
+
+impl HamtShard {
+    fn insert(&mut self, link: Link, depth: usize) -> Result<()> {
+        let index = hamt_index(&link.name, depth)?;
+
+        match self.entries.remove(&index) {
+            None => {
+                self.entries.insert(index, HamtEntry::Link(link));
+            }
+            Some(HamtEntry::Link(existing)) => {
+                let mut shard = HamtShard::default();
+                shard.insert(existing, depth + 1)?;
+                shard.insert(link, depth + 1)?;
+                self.entries.insert(index, HamtEntry::Shard(shard));
+            }
+            Some(HamtEntry::Shard(mut shard)) => {
+                shard.insert(link, depth + 1)?;
+                self.entries.insert(index, HamtEntry::Shard(shard));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_to(&self, dag: &mut Dag) -> Link {
+        let mut bitfield = [0u8; HAMT_FANOUT as usize / 8];
+        let links = self
+            .entries
+            .iter()
+            .map(|(index, entry)| {
+                bitfield[bitfield.len() - 1 - *index as usize / 8] |= 1 << (index % 8);
+
+                match entry {
+                    HamtEntry::Link(l) => Link {
+                        name: format!("{:02X}{}", index, l.name),
+                        ..l.clone()
+                    },
+                    HamtEntry::Shard(shard) => Link {
+                        name: format!("{:02X}", index),
+                        ..shard.add_to(dag)
+                    },
+                }
+            })
+            .collect::<Vec<Link>>();
+
+        let mut unixfs = vec![];
+        write_varint_field(&mut unixfs, 1, UNIXFS_HAMT_SHARD);
+        write_bytes_field(&mut unixfs, 2, &bitfield);
+        write_varint_field(&mut unixfs, 5, HAMT_HASH_MURMUR3);
+        write_varint_field(&mut unixfs, 6, HAMT_FANOUT);
+
+        add_node(dag, &links, &unixfs)
+    }
+}
+
+/// Shards large directories like the reference implementation, by the murmur3 hash of the names.
+fn hamt_directory(dag: &mut Dag, links: &[Link]) -> Result<Link> {
+    let mut shard = HamtShard::default();
+    for link in links {
+        shard.insert(link.clone(), 0)?;
+    }
+
+    Ok(shard.add_to(dag))
+}
+
+/// Index (one byte of the 64 bit murmur3 hash per level) of the name in the shard of the depth.
+fn hamt_index(name: &str, depth: usize) -> Result<u8> {
+    let hash = murmur3::murmur3_x64_128(&mut Cursor::new(name.as_bytes()), 0)? as u64;
+
+    match hash.to_be_bytes().get(depth) {
+        Some(index) => Ok(*index),
+        None => bail!("Directory entry ({}) can't be sharded", name),
+    }
+}
+
+/// Adds a dag-pb node with the links and UnixFS data, linked by its cumulative size.
+fn add_node(dag: &mut Dag, links: &[Link], unixfs: &[u8]) -> Link {
+    let mut node = vec![];
+    for l in links {
+        let mut pb_link = vec![];
+        write_bytes_field(&mut pb_link, 1, &l.cid.to_bytes());
+        write_bytes_field(&mut pb_link, 2, l.name.as_bytes());
+        write_varint_field(&mut pb_link, 3, l.size);
+        write_bytes_field(&mut node, 2, &pb_link);
+    }
+    write_bytes_field(&mut node, 1, unixfs);
+
+    let size = node.len() as u64 + links.iter().map(|l| l.size).sum::<u64>();
+
+    link("", dag.add_node(node), size)
+}
+
+fn link(name: &str, cid: Cid, size: u64) -> Link {
+    Link {
+        name: name.to_string(),
+        cid,
+        size,
+    }
+}
+
+fn write_varint_field(buffer: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(buffer, field << 3);
+    write_varint(buffer, value);
+}
+
+fn write_bytes_field(buffer: &mut Vec<u8>, field: u64, value: &[u8]) {
+    write_varint(buffer, (field << 3) | 2);
+    write_varint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn empty_directory_matches_reference() {
+        let mut dag = Dag::default();
+        let link = basic_directory(&mut dag, &[]);
+
+        assert_eq!(
+            link.cid.to_string(),
+            "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354"
+        );
+        assert_eq!(link.size, 4);
+    }
+
+    #[test]
+    fn hamt_index_uses_murmur3_hash() {
+        // murmur3 x64 128 ("hello") = 0xcbd8a7b341bd9b02...
+        assert_eq!(hamt_index("hello", 0).unwrap(), 0xcb);
+        assert_eq!(hamt_index("hello", 7).unwrap(), 0x02);
+        assert!(hamt_index("hello", 8).is_err());
+    }
+
+    #[test]
+    fn multi_chunk_file_matches_reference() {
+        let dir = TempDir::new("unixfs_file");
+        let path = dir.join("big.bin");
+        // 600 KiB, so the file is split into three chunks
+        let content = (0..600 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        write(&path, content).unwrap();
+
+        let mut dag = Dag::default();
+        let link = import_file(&mut dag, &path).unwrap();
+
+        assert_eq!(
+            link.cid.to_string(),
+            "bafybeib7rtiuapefdrbklxz5ysik3bixpon5h6cdrim7jhclqfyaz2t5cu"
+        );
+        assert_eq!(link.size, 614558);
+        // three raw chunks and the file node
+        assert_eq!(dag.get_blocks().len(), 4);
+    }
+
+    #[test]
+    fn nested_directory_matches_reference() {
+        let dir = TempDir::new("unixfs_directory");
+        let root = dir.join("root");
+        create_dir_all(root.join("sub")).unwrap();
+        write(root.join("a.txt"), "hello\n").unwrap();
+        write(root.join("sub").join("b.txt"), "world\n").unwrap();
+        write(root.join("sub").join("c.txt"), "").unwrap();
+
+        let mut dag = Dag::default();
+        let (link, files) = import_directory(&mut dag, &root, &|_| true).unwrap();
+
+        assert_eq!(
+            link.cid.to_string(),
+            "bafybeibemj2mmahatgsuv3ulujsdfaisboogdlzmbiikwgqz75sjiysjiq"
+        );
+        assert_eq!(link.size, 214);
+        assert_eq!(
+            files
+                .iter()
+                .map(|f| (f.name.as_str(), f.cid.to_string()))
+                .collect::<Vec<(&str, String)>>(),
+            vec![
+                (
+                    "a.txt",
+                    "bafkreicysg23kiwv34eg2d7qweipxwosdo2py4ldv42nbauguluen5v6am".to_string()
+                ),
+                (
+                    "sub/b.txt",
+                    "bafkreihcldjer7njjrrxknqh67cestxa7s7jf4nhnp62y6k4twcbahvtc4".to_string()
+                ),
+                (
+                    "sub/c.txt",
+                    "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hamt_directory_matches_reference() {
+        // 2000 entries collide in the first level, so the shard has nested shards
+        let links = (0..2000)
+            .map(|i| {
+                let name = format!("{}.png", i);
+                let cid = Cid::new(Codec::Raw, name.as_bytes());
+
+                link(&name, cid, name.len() as u64)
+            })
+            .collect::<Vec<Link>>();
+
+        let mut dag = Dag::default();
+        let link = hamt_directory(&mut dag, &links).unwrap();
+
+        assert_eq!(
+            link.cid.to_string(),
+            "bafybeidjcv3zwunsjnqiourbvtgl4p73lkp2us6iu4qtxfrbunvl5frzpy"
+        );
+        assert_eq!(link.size, 147023);
+        assert!(dag.get_blocks().len() > 1);
+    }
+}
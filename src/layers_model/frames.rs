@@ -0,0 +1,183 @@
+use std::fs::{read, read_dir, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{open, AnimationDecoder, DynamicImage, RgbImage, RgbaImage};
+
+use crate::layers_model::is_sidecar_file;
+
+/// Decoded frames of a layer file, a still image has a single frame without delay.
+#[derive(Debug, Clone)]
+pub struct LayerFrames {
+    frames: Vec<RgbaImage>,
+    /// Display duration (in ms) of every frame, 0 if the frame has no own delay.
+    delays: Vec<u32>,
+}
+
+impl LayerFrames {
+    pub fn still(image: RgbaImage) -> LayerFrames {
+        LayerFrames {
+            frames: vec![image],
+            delays: vec![0],
+        }
+    }
+
+    /// Reads an animated gif, png (APNG) or webp file, a directory of frame images, or a still
+    /// image.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<LayerFrames> {
+        let path = path.as_ref();
+        let context = format!("Read layer frames ({})", path.display());
+
+        let frames = if path.is_dir() {
+            read_frame_dir(path).context(context)?
+        } else {
+            let extension = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+
+            match extension.as_str() {
+                "gif" => read_gif(path).context(context)?,
+                "png" | "apng" => read_png(path).context(context)?,
+                "webp" => read_webp(path).context(context)?,
+                _ => LayerFrames::still(open(path).context(context)?.into_rgba8()),
+            }
+        };
+
+        if frames.frames.is_empty() {
+            bail!("Layer file ({}) has no frames", path.display());
+        }
+
+        Ok(frames)
+    }
+
+    pub fn get_frames(&self) -> &Vec<RgbaImage> {
+        &self.frames
+    }
+
+    pub fn get_delays(&self) -> &Vec<u32> {
+        &self.delays
+    }
+
+    /// Dimensions of the first frame.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames[0].dimensions()
+    }
+
+    /// Number of bytes of the decoded pixel data of all frames.
+    pub fn byte_size(&self) -> usize {
+        self.frames.iter().map(|f| f.as_raw().len()).sum()
+    }
+
+    /// Applies the (e.g. transform) function to every frame, keeping the delays.
+    pub fn map<F: Fn(&RgbaImage) -> RgbaImage>(&self, f: F) -> LayerFrames {
+        LayerFrames {
+            frames: self.frames.iter().map(f).collect(),
+            delays: self.delays.clone(),
+        }
+    }
+}
+
+fn read_frame_dir(path: &Path) -> Result<LayerFrames> {
+    let mut frame_paths = read_dir(path)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && !is_sidecar_file(p))
+        .collect::<Vec<PathBuf>>();
+    // numbered frames (e.g. 2.png before 10.png) are ordered by their number
+    frame_paths.sort_by_key(|p| {
+        let number = p
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u64>().ok());
+
+        (number.is_none(), number, p.clone())
+    });
+
+    let frames = frame_paths
+        .iter()
+        .map(|p| {
+            Ok(open(p)
+                .context(format!("Read frame ({})", p.display()))?
+                .into_rgba8())
+        })
+        .collect::<Result<Vec<RgbaImage>>>()?;
+
+    Ok(LayerFrames {
+        delays: vec![0; frames.len()],
+        frames,
+    })
+}
+
+fn read_gif(path: &Path) -> Result<LayerFrames> {
+    let decoder = GifDecoder::new(BufReader::new(File::open(path)?))?;
+
+    from_frames(decoder.into_frames().collect_frames()?)
+}
+
+fn read_png(path: &Path) -> Result<LayerFrames> {
+    let decoder = PngDecoder::new(BufReader::new(File::open(path)?))?;
+    if !decoder.is_apng() {
+        return Ok(LayerFrames::still(open(path)?.into_rgba8()));
+    }
+
+    from_frames(decoder.apng().into_frames().collect_frames()?)
+}
+
+fn read_webp(path: &Path) -> Result<LayerFrames> {
+    let data = read(path)?;
+    let is_animated = webp::BitstreamFeatures::new(&data).is_some_and(|f| f.has_animation());
+
+    if !is_animated {
+        let image = webp::Decoder::new(&data)
+            .decode()
+            .context("Couldn't decode webp image")?;
+
+        let (width, height) = (image.width(), image.height());
+        let still = if image.is_alpha() {
+            RgbaImage::from_raw(width, height, image.to_vec())
+        } else {
+            RgbImage::from_raw(width, height, image.to_vec())
+                .map(|i| DynamicImage::ImageRgb8(i).into_rgba8())
+        };
+
+        return Ok(LayerFrames::still(still.context("Invalid webp image")?));
+    }
+
+    let animation = webp::AnimDecoder::new(&data)
+        .decode()
+        .map_err(|err| anyhow!("Couldn't decode webp animation: {}", err))?;
+    let mut frames = vec![];
+    let mut delays = vec![];
+    let mut previous_timestamp = 0;
+    // timestamps of decoded webp frames are the end time of the frame
+    for index in 0..animation.len() {
+        let frame = animation.get_frame(index).context("Missing webp frame")?;
+        let image = RgbaImage::from_raw(frame.width(), frame.height(), frame.get_image().to_vec())
+            .context("Invalid webp frame")?;
+
+        delays.push((frame.get_time_ms() - previous_timestamp).max(0) as u32);
+        frames.push(image);
+        previous_timestamp = frame.get_time_ms();
+    }
+
+    Ok(LayerFrames { frames, delays })
+}
+
+fn from_frames(frames: Vec<image::Frame>) -> Result<LayerFrames> {
+    let delays = frames
+        .iter()
+        .map(|f| {
+            let (numer, denom) = f.delay().numer_denom_ms();
+            numer / denom.max(1)
+        })
+        .collect();
+
+    Ok(LayerFrames {
+        frames: frames.into_iter().map(|f| f.into_buffer()).collect(),
+        delays,
+    })
+}
@@ -6,32 +6,68 @@ use anyhow::{Context, Result};
 use rand::prelude::*;
 
 use crate::config::app::AppConfiguration;
+use crate::config::edition::TransformConfiguration;
 use crate::logger::log_warn;
 
-#[derive(Debug)]
+pub use frames::LayerFrames;
+
+mod frames;
+
+const RARITY_DELIMITER: char = '#';
+const DEFAULT_RARITY_WEIGHT: u32 = 1;
+const SIDECAR_EXTENSION: &str = "json";
+
+#[derive(Debug, Default)]
 pub struct Layers {
     layers: Vec<Layer>,
 }
 
 impl Layers {
-    pub fn get_rng_files(&self, name: &str, min: u32, max: u32) -> Vec<RngLayerFile> {
+    /// Picks between `min` and `max` weighted random files of the layer, only files whose
+    /// name passes the `filter` are candidates.
+    pub fn get_rng_files<R: Rng, F: Fn(&str) -> bool>(
+        &self,
+        name: &str,
+        min: u32,
+        max: u32,
+        rng: &mut R,
+        filter: F,
+    ) -> Vec<RngLayerFile> {
         match self.find_layer(name) {
             Some(layer) => {
-                let mut rng = rand::thread_rng();
                 let amount = if min == max {
                     min
                 } else {
                     rng.gen_range(min..=max)
                 };
 
+                let candidates = layer
+                    .files
+                    .iter()
+                    .filter(|f| filter(&f.name))
+                    .collect::<Vec<&LayerFile>>();
+
+                let files =
+                    match candidates.choose_multiple_weighted(rng, amount as usize, |f| f.weight) {
+                        Ok(files) => files.copied().collect::<Vec<&LayerFile>>(),
+                        Err(err) => {
+                            log_warn(format!(
+                                "Invalid rarity weights in layer: {} ({})",
+                                name, err
+                            ));
+
+                            vec![]
+                        }
+                    };
+
                 sort_utility(
-                    layer
-                        .files
-                        .choose_multiple(&mut rand::thread_rng(), amount as usize)
-                        .map(|p| p.to_path_buf())
-                        .map(|p| RngLayerFile {
+                    files
+                        .into_iter()
+                        .map(|f| RngLayerFile {
                             layer: name.to_string(),
-                            path: p,
+                            name: f.name.to_string(),
+                            path: f.path.to_path_buf(),
+                            transform: f.transform.clone(),
                         })
                         .collect(),
                 )
@@ -44,6 +80,88 @@ impl Layers {
         }
     }
 
+    /// Picks a single file of the layer, weighted by the `weight` of its name and rarity weight.
+    /// Nothing is picked, if no file has a positive weight.
+    pub fn get_weighted_file<R: Rng, F: Fn(&str, u32) -> f64>(
+        &self,
+        name: &str,
+        rng: &mut R,
+        weight: F,
+    ) -> Option<RngLayerFile> {
+        let file = self
+            .find_layer(name)?
+            .files
+            .choose_weighted(rng, |f| weight(&f.name, f.weight))
+            .ok()?;
+
+        Some(RngLayerFile {
+            layer: name.to_string(),
+            name: file.name.to_string(),
+            path: file.path.to_path_buf(),
+            transform: file.transform.clone(),
+        })
+    }
+
+    /// Files of the layer with the given names (without rarity weight), in the order they are
+    /// picked in.
+    pub fn get_files(&self, name: &str, file_names: &[&str]) -> Result<Vec<RngLayerFile>> {
+        let layer = self
+            .find_layer(name)
+            .context(format!("Couldn't find layer with name: {}", name))?;
+
+        let files = file_names
+            .iter()
+            .map(|file_name| {
+                let file = layer
+                    .files
+                    .iter()
+                    .find(|f| f.name == *file_name)
+                    .context(format!(
+                        "Couldn't find file ({}) in layer ({})",
+                        file_name, name
+                    ))?;
+
+                Ok(RngLayerFile {
+                    layer: name.to_string(),
+                    name: file.name.to_string(),
+                    path: file.path.to_path_buf(),
+                    transform: file.transform.clone(),
+                })
+            })
+            .collect::<Result<Vec<RngLayerFile>>>()?;
+
+        Ok(sort_utility(files))
+    }
+
+    /// Names (without rarity weight) of all files of the layer, if the layer exists.
+    pub fn get_file_names(&self, name: &str) -> Option<Vec<&str>> {
+        self.find_layer(name)
+            .map(|l| l.files.iter().map(|f| f.name.as_str()).collect())
+    }
+
+    /// Names (without rarity weight) and rarity weights of all files of the layer.
+    pub fn get_file_weights(&self, name: &str) -> Option<Vec<(&str, u32)>> {
+        self.find_layer(name).map(|l| {
+            l.files
+                .iter()
+                .map(|f| (f.name.as_str(), f.weight))
+                .collect()
+        })
+    }
+
+    /// Paths and sidecar transforms of all files of the layer.
+    pub fn get_file_paths(
+        &self,
+        name: &str,
+    ) -> Option<Vec<(&Path, Option<&TransformConfiguration>)>> {
+        self.find_layer(name).map(|l| {
+            l.files
+                .iter()
+                .map(|f| (f.path.as_path(), f.transform.as_ref()))
+                .collect()
+        })
+    }
+
     fn find_layer(&self, name: &str) -> Option<&Layer> {
         self.layers.iter().find(|l| l._name == name)
     }
@@ -87,22 +205,38 @@ impl Layers {
 #[derive(Debug)]
 pub struct RngLayerFile {
     layer: String,
+    name: String,
     path: PathBuf,
+    transform: Option<TransformConfiguration>,
 }
 
 impl RngLayerFile {
     pub fn get_layer(&self) -> &str {
         &self.layer
     }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
     pub fn get_path(&self) -> &Path {
         &self.path
     }
+    pub fn get_transform(&self) -> Option<&TransformConfiguration> {
+        self.transform.as_ref()
+    }
 }
 
 #[derive(Debug)]
 struct Layer {
     _name: String,
-    files: Vec<PathBuf>,
+    files: Vec<LayerFile>,
+}
+
+#[derive(Debug)]
+struct LayerFile {
+    name: String,
+    path: PathBuf,
+    weight: u32,
+    transform: Option<TransformConfiguration>,
 }
 
 impl Layer {
@@ -115,13 +249,67 @@ impl Layer {
         read_dir(dir_entry.path())
             .context(context)?
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().is_file())
-            .for_each(|e| files.push(e.path()));
+            // directories of a layer contain the frames of an animated layer file
+            .filter(|e| e.path().is_dir() || !is_sidecar_file(&e.path()))
+            .for_each(|e| {
+                let path = e.path();
+                let file_name = e.file_name().to_string_lossy().to_string();
+                let (name, weight) = split_rarity_weight(file_name.split('.').next().unwrap_or(""));
+                let transform = read_sidecar_transform(&path);
+
+                files.push(LayerFile {
+                    name: name.to_string(),
+                    path,
+                    weight,
+                    transform,
+                })
+            });
+
+        // sort files to keep the selection reproducible for seeded runs
+        files.sort_by(|a, b| a.path.cmp(&b.path));
 
         Ok(Layer { _name: name, files })
     }
 }
 
+/// Splits a layer file name like `Red Eyes#20` into its name and rarity weight.
+/// Files without a (valid) weight suffix fall back to the default weight.
+pub fn split_rarity_weight(name: &str) -> (&str, u32) {
+    match name.rsplit_once(RARITY_DELIMITER) {
+        Some((clean_name, weight)) => match weight.parse::<u32>() {
+            Ok(weight) => (clean_name, weight),
+            Err(_) => (name, DEFAULT_RARITY_WEIGHT),
+        },
+        None => (name, DEFAULT_RARITY_WEIGHT),
+    }
+}
+
+fn is_sidecar_file(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == SIDECAR_EXTENSION)
+}
+
+/// Reads the transform of a layer file from the sidecar JSON next to it, e.g. `Hat#5.json`
+/// for `Hat#5.png`.
+fn read_sidecar_transform(path: &Path) -> Option<TransformConfiguration> {
+    let sidecar = path.with_extension(SIDECAR_EXTENSION);
+    if !sidecar.is_file() {
+        return None;
+    }
+
+    match TransformConfiguration::try_from_path(&sidecar) {
+        Ok(transform) => Some(transform),
+        Err(err) => {
+            log_warn(format!(
+                "Couldn't read layer file transform: {}\n{:#}",
+                sidecar.display(),
+                err
+            ));
+
+            None
+        }
+    }
+}
+
 fn try_convert_os_string_to_string(string: OsString) -> Result<String> {
     let converted = string
         .to_str()
@@ -136,3 +324,16 @@ fn sort_utility(mut files: Vec<RngLayerFile>) -> Vec<RngLayerFile> {
 
     files
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_rarity_weight_from_name() {
+        assert_eq!(split_rarity_weight("Red Eyes#20"), ("Red Eyes", 20));
+        assert_eq!(split_rarity_weight("Red Eyes"), ("Red Eyes", 1));
+        assert_eq!(split_rarity_weight("Red#Eyes"), ("Red#Eyes", 1));
+        assert_eq!(split_rarity_weight("Red#Eyes#0"), ("Red#Eyes", 0));
+    }
+}
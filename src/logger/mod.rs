@@ -20,13 +20,19 @@ pub fn log_measure<S: AsRef<str>>(message: S) -> LogMeasure {
 pub fn log_info<S: AsRef<str>>(message: S) {
     let message = format_log_message(message);
 
-    println!("{} {:10} {}", time(), "Info".bright_blue(), message);
+    eprintln!("{} {:10} {}", time(), "Info".bright_blue(), message);
 }
 
 pub fn log_warn<S: AsRef<str>>(message: S) {
     let message = format_log_message(message);
 
-    println!("{} {:10} {}", time(), "Warning".bright_yellow(), message);
+    eprintln!("{} {:10} {}", time(), "Warning".bright_yellow(), message);
+}
+
+pub fn log_error<S: AsRef<str>>(message: S) {
+    let message = format_log_message(message);
+
+    eprintln!("{} {:10} {}", time(), "Error".bright_red(), message);
 }
 
 fn format_log_message<S: AsRef<str>>(message: S) -> String {
@@ -72,7 +78,7 @@ impl LogMeasure {
             }
         };
 
-        println!(
+        eprintln!(
             "{time} {measure:10} '{message}' {suffix}",
             time = time,
             measure = measure.white(),
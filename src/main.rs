@@ -4,11 +4,17 @@ extern crate anyhow;
 use anyhow::Result;
 
 mod app;
+mod capacity;
 mod config;
 mod hashing;
+mod ipfs;
 mod layers_model;
 mod logger;
 mod processor;
+mod validator;
+
+#[cfg(test)]
+mod test_utils;
 
 #[tokio::main]
 async fn main() -> Result<()> {
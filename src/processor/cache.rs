@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use lru::LruCache;
+
+use crate::layers_model::LayerFrames;
+use crate::logger::log_info;
+use crate::processor::lock_ignoring_poison;
+
+/// Caches decoded layer images shared across all editions (and worker threads),
+/// so popular layer files are only decoded once as long as they fit into the memory cap.
+pub(super) struct LayerCache {
+    capacity: usize,
+    state: Mutex<LayerCacheState>,
+}
+
+struct LayerCacheState {
+    images: LruCache<(PathBuf, String), Arc<LayerFrames>>,
+    used: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl LayerCache {
+    /// Creates a cache holding up to `capacity` bytes of decoded pixel data.
+    pub(super) fn new(capacity: usize) -> LayerCache {
+        LayerCache {
+            capacity,
+            state: Mutex::new(LayerCacheState {
+                images: LruCache::unbounded(),
+                used: 0,
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            }),
+        }
+    }
+
+    pub(super) fn get<P: AsRef<Path>>(&self, path: P) -> Result<Arc<LayerFrames>> {
+        self.get_or_insert(&path, "", || read_layer_frames(path.as_ref()))
+    }
+
+    /// Gets a variant (e.g. a transformed version) of the layer image, identified by the `variant`
+    /// key and created from every decoded frame of the layer image on a miss. Only the variant is
+    /// cached, the original is reused if already cached but not inserted.
+    pub(super) fn get_variant<P: AsRef<Path>, F: Fn(&RgbaImage) -> RgbaImage>(
+        &self,
+        path: P,
+        variant: &str,
+        create: F,
+    ) -> Result<Arc<LayerFrames>> {
+        self.get_or_insert(&path, variant, || {
+            let key = (path.as_ref().to_path_buf(), String::new());
+            let cached = self.lock().images.peek(&key).cloned();
+            let frames = match cached {
+                Some(frames) => frames,
+                None => Arc::new(read_layer_frames(path.as_ref())?),
+            };
+
+            Ok(frames.map(create))
+        })
+    }
+
+    fn get_or_insert<P: AsRef<Path>, F: FnOnce() -> Result<LayerFrames>>(
+        &self,
+        path: P,
+        variant: &str,
+        create: F,
+    ) -> Result<Arc<LayerFrames>> {
+        let key = (path.as_ref().to_path_buf(), variant.to_string());
+
+        {
+            let mut state = self.lock();
+            if let Some(image) = state.images.get(&key).cloned() {
+                state.hits += 1;
+                return Ok(image);
+            }
+            state.misses += 1;
+        }
+
+        // create without holding the lock, so other workers are not blocked meanwhile
+        let image = Arc::new(create()?);
+        let image_size = image.byte_size();
+
+        if image_size <= self.capacity {
+            let mut state = self.lock();
+            if let Some(replaced) = state.images.put(key, image.clone()) {
+                state.used -= replaced.byte_size();
+            }
+            state.used += image_size;
+
+            while state.used > self.capacity {
+                match state.images.pop_lru() {
+                    Some((_, evicted)) => {
+                        state.used -= evicted.byte_size();
+                        state.evictions += 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    pub(super) fn log_stats(&self) {
+        let state = self.lock();
+        let requests = state.hits + state.misses;
+        let hit_rate = if requests == 0 {
+            0.0
+        } else {
+            state.hits as f64 / requests as f64 * 100.0
+        };
+
+        log_info(format!(
+            "Layer cache: {} hits, {} misses ({:.1}% hit rate), {} evictions, {} images ({:.1} MB) cached",
+            state.hits,
+            state.misses,
+            hit_rate,
+            state.evictions,
+            state.images.len(),
+            state.used as f64 / (1024.0 * 1024.0)
+        ));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LayerCacheState> {
+        lock_ignoring_poison(&self.state)
+    }
+}
+
+fn read_layer_frames(path: &Path) -> Result<LayerFrames> {
+    LayerFrames::read(path).context(format!("Load layer image ({})", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Rgba};
+
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn evicts_least_recently_used_images() {
+        let dir = TempDir::new("layer_cache");
+        let paths = ["a.png", "b.png", "c.png"].map(|name| dir.join(name));
+        for path in &paths {
+            ImageBuffer::from_pixel(4, 4, Rgba([1u8, 2, 3, 4]))
+                .save(path)
+                .unwrap();
+        }
+
+        // every image takes 64 bytes, so only two of them fit into the cache
+        let cache = LayerCache::new(128);
+        cache.get(&paths[0]).unwrap();
+        cache.get(&paths[1]).unwrap();
+        cache.get(&paths[0]).unwrap();
+        cache.get(&paths[2]).unwrap();
+        cache.get(&paths[0]).unwrap();
+        cache.get(&paths[1]).unwrap();
+
+        let state = cache.lock();
+        assert_eq!(state.hits, 2);
+        assert_eq!(state.misses, 4);
+        assert_eq!(state.evictions, 2);
+        assert_eq!(state.used, 128);
+    }
+
+    #[test]
+    fn caches_variants_without_original() {
+        let dir = TempDir::new("layer_cache_variant");
+        let path = dir.join("a.png");
+        ImageBuffer::from_pixel(4, 4, Rgba([1u8, 2, 3, 4]))
+            .save(&path)
+            .unwrap();
+
+        let cache = LayerCache::new(1024);
+        cache.get_variant(&path, "flip", |i| i.clone()).unwrap();
+        cache.get_variant(&path, "flip", |i| i.clone()).unwrap();
+
+        let state = cache.lock();
+        assert_eq!(state.images.len(), 1);
+        assert_eq!(state.used, 64);
+        assert_eq!(state.hits, 1);
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::config::edition::EditionConfiguration;
+use crate::config::metadata::MetadataProfile;
+
+pub(super) const METADATA_FILE_NAME: &str = "_metadata.json";
+pub(super) const COLLECTION_FILE_NAME: &str = "collection.json";
+pub(super) const CIP25_FILE_NAME: &str = "cip25.json";
+
+#[derive(Serialize)]
+struct CollectionMeta<'a> {
+    name: &'a str,
+    description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_link: Option<&'a str>,
+    seller_fee_basis_points: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_recipient: Option<&'a str>,
+}
+
+/// Saves the metadata of all editions as one `_metadata.json` array and the contract-level
+/// `collection.json`, plus the combined `cip25.json` policy map for the CIP-25 profile. The
+/// edition metadata is read back from the `meta` directory, so editions of a resumed run are
+/// included.
+pub(super) fn save_collection_metadata<P: AsRef<Path>>(
+    edition_config: &EditionConfiguration,
+    destination_path: P,
+) -> Result<()> {
+    let destination_path = destination_path.as_ref();
+    let context = format!(
+        "Save collection metadata at ({})",
+        destination_path.display()
+    );
+    let metas = read_edition_metas(destination_path, edition_config.get_edition_size())
+        .context(context.clone())?
+        .into_iter()
+        .map(|(_, meta)| meta)
+        .collect::<Vec<Value>>();
+
+    let content = serde_json::to_string_pretty(&metas).context(context.clone())?;
+    write(destination_path.join(METADATA_FILE_NAME), content).context(context.clone())?;
+
+    let collection = edition_config.get_collection();
+    let collection_meta = CollectionMeta {
+        name: collection
+            .get_name()
+            .unwrap_or_else(|| edition_config._get_name()),
+        description: collection
+            .get_description()
+            .unwrap_or_else(|| edition_config._get_description()),
+        image: collection.get_image(),
+        external_link: collection.get_external_link(),
+        seller_fee_basis_points: collection
+            .get_seller_fee_basis_points()
+            .or_else(|| edition_config.get_metadata().get_seller_fee_basis_points())
+            .unwrap_or_default(),
+        fee_recipient: collection.get_fee_recipient(),
+    };
+    let content = serde_json::to_string_pretty(&collection_meta).context(context.clone())?;
+    write(destination_path.join(COLLECTION_FILE_NAME), content).context(context.clone())?;
+
+    if edition_config.get_metadata().get_profile() == MetadataProfile::Cip25 {
+        let content =
+            serde_json::to_string_pretty(&cip25_policy_map(&metas)).context(context.clone())?;
+        write(destination_path.join(CIP25_FILE_NAME), content).context(context)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the metadata files of the editions `1..=edition_size`, stale files of larger previous
+/// runs are ignored, just like editions left out by an unfilled layer configuration.
+pub(super) fn read_edition_metas(
+    destination_path: &Path,
+    edition_size: u32,
+) -> Result<Vec<(u32, Value)>> {
+    let meta_dir = destination_path.join("meta");
+
+    (1..=edition_size)
+        .map(|edition| (edition, meta_dir.join(format!("{}.json", edition))))
+        .filter(|(_, path)| path.is_file())
+        .map(|(edition, path)| {
+            let context = format!("Read edition metadata ({})", path.display());
+            let content = read_to_string(&path).context(context.clone())?;
+
+            Ok((edition, serde_json::from_str(&content).context(context)?))
+        })
+        .collect()
+}
+
+/// Edition number of an image or metadata file, e.g. `12` of `images/12.png`.
+pub(super) fn edition_number(path: &Path) -> Option<u32> {
+    path.file_stem()?.to_str()?.parse::<u32>().ok()
+}
+
+/// Merges the `721` policy maps of all editions into one map, ready to be attached to a single
+/// minting transaction.
+fn cip25_policy_map(metas: &[Value]) -> Value {
+    let mut policies = Map::new();
+
+    for edition_policies in metas
+        .iter()
+        .filter_map(|m| m.get("721").and_then(|p| p.as_object()))
+    {
+        for (policy, assets) in edition_policies {
+            match (policies.get_mut(policy), assets) {
+                (Some(Value::Object(policy_assets)), Value::Object(assets)) => {
+                    policy_assets.extend(assets.clone())
+                }
+                _ => {
+                    policies.insert(policy.to_string(), assets.clone());
+                }
+            }
+        }
+    }
+
+    json!({ "721": policies })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+
+    use super::*;
+    use crate::test_utils::{write_json, TempDir};
+
+    #[test]
+    fn cip25_policy_maps_are_merged() {
+        let metas = vec![
+            json!({ "721": { "abc": { "Nft1": { "name": "Nft #1" } }, "version": "1.0" } }),
+            json!({ "721": { "abc": { "Nft2": { "name": "Nft #2" } }, "version": "1.0" } }),
+        ];
+
+        assert_eq!(
+            cip25_policy_map(&metas),
+            json!({
+                "721": {
+                    "abc": { "Nft1": { "name": "Nft #1" }, "Nft2": { "name": "Nft #2" } },
+                    "version": "1.0"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn stale_edition_metas_are_ignored() {
+        let dir = TempDir::new("collection");
+        create_dir_all(dir.join("meta")).unwrap();
+        for edition in 1..=3 {
+            write_json(
+                &dir.join("meta").join(format!("{}.json", edition)),
+                &json!({ "name": format!("Nft #{}", edition) }),
+            );
+        }
+
+        let metas = read_edition_metas(dir.path(), 2).unwrap();
+        assert_eq!(
+            metas,
+            vec![
+                (1, json!({ "name": "Nft #1" })),
+                (2, json!({ "name": "Nft #2" }))
+            ]
+        );
+        assert_eq!(read_edition_metas(dir.path(), 4).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,403 @@
+use std::fs::read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use color_quant::NeuQuant;
+use image::codecs::jpeg::JpegEncoder;
+use image::{open, ColorType, RgbImage, RgbaImage};
+
+use crate::config::output::{AnimationFormat, OutputFormat, PngCompression};
+
+/// Signature at the start of every png file.
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// Sampling factor of the palette quantization, 1 is the slowest but best, 30 the fastest.
+const QUANTIZATION_SAMPLE_FACTOR: i32 = 10;
+
+/// Encodes the image into the bytes of the output format.
+pub(super) fn encode(image: &RgbaImage, format: &OutputFormat) -> Result<Vec<u8>> {
+    let context = format!("Encode image as {}", format.get_extension());
+
+    match format {
+        OutputFormat::Png {
+            compression,
+            palette,
+        } => encode_png(image, *compression, *palette).context(context),
+        OutputFormat::WebP { quality, lossless } => {
+            let encoder = webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height());
+            let memory = if *lossless {
+                encoder.encode_lossless()
+            } else {
+                encoder.encode(*quality)
+            };
+
+            Ok(memory.to_vec())
+        }
+        OutputFormat::Jpeg {
+            quality,
+            background,
+        } => {
+            let flattened = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+                let pixel = image.get_pixel(x, y);
+                let alpha = pixel[3] as f32 / 255.0;
+                let mut flattened = *background;
+                for c in 0..3 {
+                    flattened[c] = (pixel[c] as f32 * alpha + background[c] as f32 * (1.0 - alpha))
+                        .round() as u8;
+                }
+
+                flattened
+            });
+
+            let mut bytes = vec![];
+            JpegEncoder::new_with_quality(&mut bytes, *quality)
+                .encode(
+                    flattened.as_raw(),
+                    image.width(),
+                    image.height(),
+                    ColorType::Rgb8,
+                )
+                .context(context)?;
+
+            Ok(bytes)
+        }
+    }
+}
+
+/// Encodes the frames (and their delays in ms) into an animation, which loops infinitely.
+pub(super) fn encode_animation(
+    frames: &[(RgbaImage, u32)],
+    format: &AnimationFormat,
+) -> Result<Vec<u8>> {
+    let context = format!("Encode animation as {}", format.get_extension());
+
+    match format {
+        AnimationFormat::Gif => encode_gif(frames).context(context),
+        AnimationFormat::Apng => encode_apng(frames).context(context),
+        AnimationFormat::WebP { quality, lossless } => {
+            encode_animated_webp(frames, *quality, *lossless).context(context)
+        }
+    }
+}
+
+/// Whether the image file of the output format can be decoded completely.
+pub(super) fn is_readable<P: AsRef<Path>>(path: P, format: &OutputFormat) -> bool {
+    match format {
+        // the webp decoder of the image crate doesn't support lossless and alpha images
+        OutputFormat::WebP { .. } => read(path)
+            .map(|data| webp::Decoder::new(&data).decode().is_some())
+            .unwrap_or(false),
+        _ => open(path).is_ok(),
+    }
+}
+
+fn encode_png(
+    image: &RgbaImage,
+    compression: PngCompression,
+    palette: Option<usize>,
+) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, image.width(), image.height());
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(match compression {
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Default => png::Compression::Default,
+        PngCompression::Best => png::Compression::Best,
+    });
+
+    let data = match palette {
+        Some(colors) => {
+            let quantizer = NeuQuant::new(QUANTIZATION_SAMPLE_FACTOR, colors, image.as_raw());
+            let color_map = quantizer.color_map_rgba();
+
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_palette(color_map.chunks(4).flat_map(|c| c[..3].to_vec()).collect());
+            encoder.set_trns(color_map.chunks(4).map(|c| c[3]).collect());
+
+            image
+                .pixels()
+                .map(|p| quantizer.index_of(&p.0) as u8)
+                .collect()
+        }
+        None => {
+            encoder.set_color(png::ColorType::RGBA);
+
+            image.as_raw().to_vec()
+        }
+    };
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    drop(writer);
+
+    Ok(bytes)
+}
+
+fn encode_gif(frames: &[(RgbaImage, u32)]) -> Result<Vec<u8>> {
+    let (width, height) = frames
+        .first()
+        .context("Animation has no frames")?
+        .0
+        .dimensions();
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        bail!("Gif animations can't exceed {}px", u16::MAX);
+    }
+
+    let mut bytes = vec![];
+    {
+        let mut encoder = gif::Encoder::new(&mut bytes, width as u16, height as u16, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        for (image, delay) in frames {
+            let mut pixels = image.as_raw().clone();
+            let mut frame =
+                gif::Frame::from_rgba_speed(width as u16, height as u16, &mut pixels, 10);
+            // gif delays are in units of 10ms
+            frame.delay = ((delay + 5) / 10).min(u16::MAX as u32) as u16;
+            // every frame covers the whole canvas, transparent pixels must not show the previous one
+            frame.dispose = gif::DisposalMethod::Background;
+            encoder.write_frame(&frame)?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Encodes every frame as png and reassembles the image data chunks into an APNG, the first
+/// frame is the still image shown by viewers without APNG support.
+fn encode_apng(frames: &[(RgbaImage, u32)]) -> Result<Vec<u8>> {
+    let (width, height) = frames
+        .first()
+        .context("Animation has no frames")?
+        .0
+        .dimensions();
+    let mut bytes = PNG_SIGNATURE.to_vec();
+    let mut sequence = 0u32;
+
+    for (index, (image, delay)) in frames.iter().enumerate() {
+        let png = encode_png(image, PngCompression::Default, None)?;
+        let chunks = png_chunks(&png)?;
+
+        if index == 0 {
+            let header = chunks
+                .iter()
+                .find(|(kind, _)| kind == b"IHDR")
+                .context("Missing png header")?;
+            write_png_chunk(&mut bytes, b"IHDR", header.1);
+
+            let mut animation_control = (frames.len() as u32).to_be_bytes().to_vec();
+            // 0 plays loop infinitely
+            animation_control.extend(0u32.to_be_bytes());
+            write_png_chunk(&mut bytes, b"acTL", &animation_control);
+        }
+
+        let mut frame_control = sequence.to_be_bytes().to_vec();
+        frame_control.extend(width.to_be_bytes());
+        frame_control.extend(height.to_be_bytes());
+        frame_control.extend(0u32.to_be_bytes());
+        frame_control.extend(0u32.to_be_bytes());
+        frame_control.extend((((*delay).min(u16::MAX as u32)) as u16).to_be_bytes());
+        frame_control.extend(1000u16.to_be_bytes());
+        // no disposal, the source replaces the whole canvas instead of being blended over it
+        frame_control.extend([0u8, 0u8]);
+        write_png_chunk(&mut bytes, b"fcTL", &frame_control);
+        sequence += 1;
+
+        for (_, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+            if index == 0 {
+                write_png_chunk(&mut bytes, b"IDAT", data);
+            } else {
+                let mut frame_data = sequence.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(data);
+                write_png_chunk(&mut bytes, b"fdAT", &frame_data);
+                sequence += 1;
+            }
+        }
+    }
+    write_png_chunk(&mut bytes, b"IEND", &[]);
+
+    Ok(bytes)
+}
+
+/// Encodes every frame as webp and wraps the image data chunks into animation frames, so every
+/// frame keeps its exact duration.
+fn encode_animated_webp(
+    frames: &[(RgbaImage, u32)],
+    quality: f32,
+    lossless: bool,
+) -> Result<Vec<u8>> {
+    let (width, height) = frames
+        .first()
+        .context("Animation has no frames")?
+        .0
+        .dimensions();
+    let mut chunks = vec![];
+
+    let mut canvas = vec![0x02 | 0x10]; // animation and alpha flags
+    canvas.extend([0u8; 3]);
+    canvas.extend(&(width - 1).to_le_bytes()[..3]);
+    canvas.extend(&(height - 1).to_le_bytes()[..3]);
+    write_webp_chunk(&mut chunks, b"VP8X", &canvas);
+
+    // transparent background, 0 loops infinitely
+    write_webp_chunk(&mut chunks, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+
+    for (image, delay) in frames {
+        let encoder = webp::Encoder::from_rgba(image.as_raw(), width, height);
+        let still = if lossless {
+            encoder.encode_lossless()
+        } else {
+            encoder.encode(quality)
+        };
+
+        let mut frame = vec![0u8; 6];
+        frame.extend(&(width - 1).to_le_bytes()[..3]);
+        frame.extend(&(height - 1).to_le_bytes()[..3]);
+        frame.extend(&(*delay).min(0xff_ffff).to_le_bytes()[..3]);
+        // no blending, every frame replaces the whole canvas
+        frame.push(0x02);
+        for (kind, data) in webp_chunks(&still)? {
+            if kind != *b"VP8X" {
+                write_webp_chunk(&mut frame, &kind, data);
+            }
+        }
+        write_webp_chunk(&mut chunks, b"ANMF", &frame);
+    }
+
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend((chunks.len() as u32 + 4).to_le_bytes());
+    bytes.extend(b"WEBP");
+    bytes.extend(chunks);
+
+    Ok(bytes)
+}
+
+fn webp_chunks(webp: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = vec![];
+    let mut offset = 12;
+
+    while offset + 8 <= webp.len() {
+        let kind: [u8; 4] = webp[offset..offset + 4].try_into()?;
+        let length = u32::from_le_bytes(webp[offset + 4..offset + 8].try_into()?) as usize;
+        let data = webp
+            .get(offset + 8..offset + 8 + length)
+            .context("Truncated webp chunk")?;
+
+        chunks.push((kind, data));
+        // chunks are padded to an even size
+        offset += 8 + length + (length & 1);
+    }
+
+    Ok(chunks)
+}
+
+fn write_webp_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend(kind);
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+fn png_chunks(png: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = vec![];
+    let mut offset = PNG_SIGNATURE.len();
+
+    while offset + 12 <= png.len() {
+        let length = u32::from_be_bytes(png[offset..offset + 4].try_into()?) as usize;
+        let kind: [u8; 4] = png[offset + 4..offset + 8].try_into()?;
+        let data = png
+            .get(offset + 8..offset + 8 + length)
+            .context("Truncated png chunk")?;
+
+        chunks.push((kind, data));
+        offset += length + 12;
+    }
+
+    Ok(chunks)
+}
+
+fn write_png_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+
+    bytes.extend((data.len() as u32).to_be_bytes());
+    bytes.extend(kind);
+    bytes.extend(data);
+    bytes.extend(hasher.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::png::PngDecoder;
+    use image::{load_from_memory, AnimationDecoder, Rgba};
+
+    use super::*;
+
+    #[test]
+    fn quantized_png_keeps_transparency() {
+        let image = RgbaImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let format = OutputFormat::Png {
+            compression: PngCompression::Best,
+            palette: Some(16),
+        };
+
+        let decoded = load_from_memory(&encode(&image, &format).unwrap())
+            .unwrap()
+            .into_rgba8();
+
+        assert_eq!(decoded.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(decoded.get_pixel(7, 7)[3], 0);
+    }
+
+    #[test]
+    fn apng_keeps_frames_and_delays() {
+        let frames = vec![
+            (RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255])), 100),
+            (RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 128])), 250),
+        ];
+
+        let apng = encode_animation(&frames, &AnimationFormat::Apng).unwrap();
+        let decoded = PngDecoder::new(apng.as_slice())
+            .unwrap()
+            .apng()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[1].delay().numer_denom_ms(), (250, 1));
+        assert_eq!(decoded[1].buffer().get_pixel(3, 3), &Rgba([0, 0, 255, 128]));
+    }
+
+    #[test]
+    fn animated_webp_keeps_frames_and_delays() {
+        let frames = vec![
+            (RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255])), 100),
+            (RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255])), 250),
+        ];
+        let format = AnimationFormat::WebP {
+            quality: 90.0,
+            lossless: true,
+        };
+
+        let webp = encode_animation(&frames, &format).unwrap();
+        let decoded = webp::AnimDecoder::new(&webp).decode().unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        // timestamps of decoded frames are the end time of the frame
+        assert_eq!(decoded.get_frame(1).unwrap().get_time_ms(), 350);
+        assert_eq!(
+            &decoded.get_frame(1).unwrap().get_image()[..4],
+            &[0, 0, 255, 255]
+        );
+    }
+}
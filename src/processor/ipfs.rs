@@ -0,0 +1,147 @@
+use std::fs::write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::app::AppConfiguration;
+use crate::config::edition::EditionConfiguration;
+use crate::config::uri::UriConfiguration;
+use crate::ipfs::{pack_directory, IpfsDirectory};
+use crate::logger::log_info;
+use crate::processor::collection::edition_number;
+use crate::processor::model::image::ANIMATIONS_DIR_NAME;
+use crate::processor::uri::update_uris;
+
+pub(super) const IPFS_FILE_NAME: &str = "ipfs.json";
+
+#[derive(Serialize)]
+struct IpfsReport {
+    images: IpfsReportDirectory,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    animations: Option<IpfsReportDirectory>,
+}
+
+#[derive(Serialize)]
+struct IpfsReportDirectory {
+    cid: String,
+    car: String,
+    files: Map<String, Value>,
+}
+
+impl IpfsReportDirectory {
+    fn new(directory: &IpfsDirectory, car: &str) -> IpfsReportDirectory {
+        IpfsReportDirectory {
+            cid: directory.get_cid().to_string(),
+            car: car.to_string(),
+            files: directory
+                .get_files()
+                .iter()
+                .map(|(name, cid)| (name.to_string(), Value::String(cid.to_string())))
+                .collect(),
+        }
+    }
+}
+
+/// Packs the images (and animations) directory into CAR files, links their CIDs in the metadata
+/// (which saves the collection metadata again) and saves all CIDs as `ipfs.json`.
+pub(super) fn save_ipfs<L: AsRef<Path>, D: AsRef<Path>>(
+    edition_config: &EditionConfiguration,
+    app_config: &AppConfiguration<L, D>,
+) -> Result<()> {
+    let destination_path = app_config.get_destination_dir();
+    let context = format!("Save IPFS files at ({})", destination_path.display());
+
+    let edition_size = edition_config.get_edition_size();
+    let is_current = |path: &Path| is_current_edition(path, edition_size);
+
+    let images = pack_directory(
+        destination_path.join("images"),
+        destination_path.join("images.car"),
+        &is_current,
+    )
+    .context(context.clone())?;
+    log_info(format!(
+        "Packed images ({}) into images.car",
+        images.get_cid()
+    ));
+
+    let animations_path = destination_path.join(ANIMATIONS_DIR_NAME);
+    let animations = if animations_path.is_dir() {
+        let car_name = format!("{}.car", ANIMATIONS_DIR_NAME);
+        let animations = pack_directory(
+            animations_path,
+            destination_path.join(&car_name),
+            &is_current,
+        )
+        .context(context.clone())?;
+        log_info(format!(
+            "Packed animations ({}) into {}",
+            animations.get_cid(),
+            car_name
+        ));
+
+        Some(animations)
+    } else {
+        None
+    };
+
+    let image_uri = format!("ipfs://{}/", images.get_cid());
+    let animation_uri = animations
+        .as_ref()
+        .map(|animations| format!("ipfs://{}/", animations.get_cid()));
+    let uri_config = UriConfiguration::new(&image_uri, animation_uri.as_deref());
+    update_uris(edition_config, app_config, &uri_config).context(context.clone())?;
+
+    let report = IpfsReport {
+        images: IpfsReportDirectory::new(&images, "images.car"),
+        animations: animations
+            .as_ref()
+            .map(|a| IpfsReportDirectory::new(a, &format!("{}.car", ANIMATIONS_DIR_NAME))),
+    };
+    let content = serde_json::to_string_pretty(&report).context(context.clone())?;
+    write(destination_path.join(IPFS_FILE_NAME), content).context(context)?;
+
+    Ok(())
+}
+
+/// Whether the file belongs to an edition `1..=edition_size`, stale files of larger previous runs
+/// aren't packed.
+fn is_current_edition(path: &Path, edition_size: u32) -> bool {
+    edition_number(path).is_some_and(|edition| (1..=edition_size).contains(&edition))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{create_test_images, test_config, TempDir};
+
+    #[test]
+    fn stale_images_are_not_packed() {
+        let dir = TempDir::with_layers("ipfs_stale", &[("Background", &["Blue", "Green", "Red"])]);
+        let config = |size: u32| {
+            test_config(json!({"layers": [{"size": size, "order": [{"name": "Background"}]}]}))
+        };
+        create_test_images(&dir, &config(3), &["--ipfs"], 1).unwrap();
+
+        // a smaller run without cleanup leaves the image and metadata of edition 3 behind
+        create_test_images(&dir, &config(2), &["--ipfs"], 1).unwrap();
+
+        let out = dir.join("out");
+        assert!(out.join("images").join("3.png").is_file());
+        let report: Value =
+            serde_json::from_str(&read_to_string(out.join(IPFS_FILE_NAME)).unwrap()).unwrap();
+        let files = report["images"]["files"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        assert_eq!(files, vec!["1.png", "2.png"]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, rename, write, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::edition::EditionConfiguration;
+use crate::logger::log_warn;
+use crate::processor::lock_ignoring_poison;
+use crate::processor::model::image::Image;
+use crate::processor::model::one_of_one::OneOfOne;
+
+pub(super) const MANIFEST_FILE_NAME: &str = "manifest.jsonl";
+
+/// Saved edition as recorded in the run manifest, the private counterpart of its metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ManifestEdition {
+    edition: u32,
+    /// Index of the layer configuration, none for one of ones.
+    #[serde(default)]
+    layer_configuration: Option<usize>,
+    dna: String,
+    #[serde(default)]
+    animated: bool,
+    /// Layer and file name pairs of the picked layer files.
+    #[serde(default)]
+    layers: Vec<(String, String)>,
+    /// Trait type and value pairs, like they appear in the metadata.
+    #[serde(default)]
+    traits: Vec<(String, String)>,
+}
+
+impl ManifestEdition {
+    pub(super) fn from_image(
+        edition: u32,
+        image: &Image,
+        edition_config: &EditionConfiguration,
+        is_animated: bool,
+    ) -> ManifestEdition {
+        ManifestEdition {
+            edition,
+            layer_configuration: Some(image.get_layer_config()),
+            dna: image.get_dna().to_string(),
+            animated: is_animated,
+            layers: image
+                .get_layer_traits()
+                .iter()
+                .map(|(layer, name)| (layer.to_string(), name.to_string()))
+                .collect(),
+            traits: image.get_traits(edition_config),
+        }
+    }
+
+    pub(super) fn from_one_of_one(edition: u32, one_of_one: &OneOfOne) -> ManifestEdition {
+        ManifestEdition {
+            edition,
+            layer_configuration: None,
+            dna: one_of_one.get_dna().to_string(),
+            animated: false,
+            layers: vec![],
+            traits: one_of_one.get_traits(),
+        }
+    }
+
+    pub(super) fn get_edition(&self) -> u32 {
+        self.edition
+    }
+
+    pub(super) fn get_layer_config(&self) -> Option<usize> {
+        self.layer_configuration
+    }
+
+    pub(super) fn get_dna(&self) -> &str {
+        &self.dna
+    }
+
+    pub(super) fn is_animated(&self) -> bool {
+        self.animated
+    }
+
+    pub(super) fn get_layer_traits(&self) -> Vec<(&str, &str)> {
+        self.layers
+            .iter()
+            .map(|(layer, name)| (layer.as_str(), name.as_str()))
+            .collect()
+    }
+
+    pub(super) fn get_traits(&self) -> &Vec<(String, String)> {
+        &self.traits
+    }
+}
+
+/// Run manifest in the destination directory, every saved edition is appended as a JSON line,
+/// so an interrupted run leaves at most a partial last line behind.
+pub(super) struct Manifest {
+    file: Mutex<File>,
+}
+
+impl Manifest {
+    /// Opens the manifest of the destination directory, a resumed run appends to the manifest of
+    /// the previous run (without its partial last line), any other run starts a new one.
+    pub(super) fn open(destination_path: &Path, resume: bool) -> Result<Manifest> {
+        let context = format!("Open run manifest in ({})", destination_path.display());
+        create_dir_all(destination_path).context(context.clone())?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(destination_path.join(MANIFEST_FILE_NAME))
+            .context(context.clone())?;
+        if resume {
+            let mut content = vec![];
+            file.read_to_end(&mut content).context(context.clone())?;
+            let length = content
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(0, |i| i + 1);
+            file.set_len(length as u64).context(context)?;
+        }
+
+        Ok(Manifest {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub(super) fn append(&self, edition: &ManifestEdition) -> Result<()> {
+        let mut line = serde_json::to_string(edition)?;
+        line.push('\n');
+
+        let mut file = lock_ignoring_poison(&self.file);
+        file.write_all(line.as_bytes())
+            .and_then(|_| file.flush())
+            .context(format!(
+                "Append edition #{} to run manifest",
+                edition.edition
+            ))?;
+
+        Ok(())
+    }
+}
+
+/// Reads the manifest of the destination directory, mapping the edition number to its latest
+/// record. Invalid (e.g. partially written) lines are skipped.
+pub(super) fn read_manifest(destination_path: &Path) -> Result<HashMap<u32, ManifestEdition>> {
+    let path = destination_path.join(MANIFEST_FILE_NAME);
+    if !path.is_file() {
+        return Ok(HashMap::new());
+    }
+    let content =
+        read_to_string(&path).context(format!("Read run manifest ({})", path.display()))?;
+
+    let mut editions = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<ManifestEdition>(line) {
+            Ok(edition) => {
+                editions.insert(edition.edition, edition);
+            }
+            Err(err) => log_warn(format!(
+                "Skip invalid line {} of the run manifest ({})",
+                index + 1,
+                err
+            )),
+        }
+    }
+
+    Ok(editions)
+}
+
+/// Replaces the manifest with the editions of the finished run, ordered by number.
+pub(super) fn save_manifest(destination_path: &Path, editions: &[ManifestEdition]) -> Result<()> {
+    let context = format!("Save run manifest in ({})", destination_path.display());
+
+    let mut content = String::new();
+    for edition in editions {
+        content.push_str(&serde_json::to_string(edition).context(context.clone())?);
+        content.push('\n');
+    }
+
+    let destination = destination_path.join(MANIFEST_FILE_NAME);
+    let temp_destination = destination.with_extension("jsonl.tmp");
+    write(&temp_destination, content).context(context.clone())?;
+    rename(&temp_destination, &destination).context(context)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    fn line(edition: u32, dna: &str) -> String {
+        format!(
+            r#"{{"edition":{},"layerConfiguration":0,"dna":"{}","layers":[["Hat","Cap"]]}}"#,
+            edition, dna
+        )
+    }
+
+    #[test]
+    fn read_manifest_skips_invalid_lines() {
+        let dir = TempDir::new("manifest");
+        let content = [
+            line(1, "a"),
+            "not json".to_string(),
+            line(2, "b"),
+            line(1, "c"),
+            line(3, "d")[..20].to_string(),
+        ]
+        .join("\n");
+        write(dir.join(MANIFEST_FILE_NAME), content).unwrap();
+
+        let editions = read_manifest(dir.path()).unwrap();
+        assert_eq!(editions.len(), 2);
+        assert_eq!(editions[&1].get_dna(), "c");
+        assert_eq!(editions[&2].get_layer_traits(), vec![("Hat", "Cap")]);
+    }
+
+    #[test]
+    fn resumed_manifest_drops_partial_last_line() {
+        let dir = TempDir::new("manifest_resume");
+        let content = format!("{}\n{}", line(1, "a"), &line(2, "b")[..20]);
+        write(dir.join(MANIFEST_FILE_NAME), content).unwrap();
+
+        let manifest = Manifest::open(dir.path(), true).unwrap();
+        let edition = serde_json::from_str::<ManifestEdition>(&line(2, "c")).unwrap();
+        manifest.append(&edition).unwrap();
+        drop(manifest);
+
+        let content = read_to_string(dir.join(MANIFEST_FILE_NAME)).unwrap();
+        assert_eq!(
+            content,
+            format!(
+                "{}\n{}\n",
+                line(1, "a"),
+                serde_json::to_string(&edition).unwrap()
+            )
+        );
+        assert_eq!(read_manifest(dir.path()).unwrap()[&2].get_dna(), "c");
+    }
+}
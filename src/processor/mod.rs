@@ -1,26 +1,73 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::remove_dir_all;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
 
 use anyhow::{Context, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 
+use crate::capacity::capacity;
 use crate::config::app::AppConfiguration;
 use crate::config::edition::EditionConfiguration;
+use crate::config::metadata::MetadataProfile;
 use crate::layers_model::Layers;
 use crate::logger::{log_info, log_warn};
+use crate::processor::cache::LayerCache;
+use crate::processor::collection::{
+    save_collection_metadata, CIP25_FILE_NAME, COLLECTION_FILE_NAME, METADATA_FILE_NAME,
+};
+use crate::processor::ipfs::{save_ipfs, IPFS_FILE_NAME};
+use crate::processor::manifest::{save_manifest, Manifest, ManifestEdition};
 use crate::processor::model::image::Image;
+use crate::processor::model::one_of_one::OneOfOne;
+use crate::processor::pinned::{drop_replaced_editions, read_pinned_images};
+use crate::processor::provenance::{ProvenanceRecord, PROVENANCE_FILE_NAME};
+use crate::processor::quota::Quotas;
+use crate::processor::rarity::RarityReport;
+use crate::processor::resume::read_complete_editions;
+use crate::validator::{check_layer_rules, check_metadata, check_one_of_ones, check_quotas};
 
+mod cache;
+mod collection;
+mod encoder;
+mod ipfs;
+mod manifest;
 mod model;
+mod pinned;
+mod provenance;
+mod quota;
+mod rarity;
+mod resume;
+mod uri;
 
-pub fn create_images<L: AsRef<Path> + Debug, D: AsRef<Path> + Debug>(
+pub use uri::update_uris;
+
+/// Number of attempts to pick all editions, while the trait quotas aren't met.
+const QUOTA_ATTEMPTS: u32 = 10;
+
+pub fn create_images<L: AsRef<Path> + Debug + Sync, D: AsRef<Path> + Debug + Sync, R: Rng>(
     layers: &Layers,
     edition_config: &EditionConfiguration,
     app_config: &AppConfiguration<L, D>,
+    rng: &mut R,
 ) -> Result<()> {
-    let mut edition_size = 0;
-    let mut edition_items = 0;
-    let mut existing_dna: Vec<String> = vec![];
-    let max_tries = app_config.get_max_tries();
+    let context = "create images";
+
+    // the DNA of pinned editions might refer to editions in the destination directory
+    let pinned = match app_config.get_pinned_file() {
+        Some(path) => {
+            let pinned =
+                read_pinned_images(layers, edition_config, app_config, path).context(context)?;
+            log_info(format!("Pinned {} editions", pinned.len()));
+
+            pinned
+        }
+        None => vec![],
+    };
 
     if app_config.is_cleanup_enabled() {
         log_info("Cleanup destination directory");
@@ -28,50 +75,579 @@ pub fn create_images<L: AsRef<Path> + Debug, D: AsRef<Path> + Debug>(
             .context("cleanup destination directory before image processing")?;
     }
 
-    for layer_config in edition_config.get_layers() {
+    let mut complete_editions = if app_config.is_resume_enabled() {
+        let complete_editions = read_complete_editions(app_config).context(context)?;
+        log_info(format!(
+            "Resume with {} complete editions",
+            complete_editions.len()
+        ));
+
+        complete_editions
+    } else {
+        HashMap::new()
+    };
+
+    let mut issues = check_layer_rules(layers, edition_config);
+    issues.append(&mut check_metadata(edition_config));
+    issues.append(&mut check_one_of_ones(edition_config));
+    issues.append(&mut check_quotas(layers, edition_config));
+    if let Some(issue) = issues.first() {
+        bail!("{}: {}", context, issue);
+    }
+    check_capacity(layers, edition_config);
+    drop_replaced_editions(&pinned, &mut complete_editions);
+    let pinned_numbers = pinned
+        .iter()
+        .map(|(edition, _)| *edition)
+        .collect::<HashSet<u32>>();
+    let one_of_ones =
+        place_one_of_ones(edition_config, &mut complete_editions, &pinned_numbers, rng)
+            .context(context)?;
+    let reserved = one_of_ones
+        .iter()
+        .map(|(edition, _)| *edition)
+        .chain(pinned_numbers)
+        .collect::<HashSet<u32>>();
+    drop_unassigned_editions(edition_config, &mut complete_editions, &reserved, &pinned);
+    let mut editions = pick_editions(
+        layers,
+        edition_config,
+        app_config,
+        &complete_editions,
+        &reserved,
+        &pinned,
+        rng,
+    )
+    .context(context)?;
+    if edition_config.is_shuffle_editions_enabled() {
+        shuffle_editions(&mut editions, rng);
+    }
+    // pinned editions are rendered first, complete ones of a resumed run are kept
+    let editions = pinned
+        .into_iter()
+        .filter(|(edition, _)| !complete_editions.contains_key(edition))
+        .chain(editions)
+        .collect::<Vec<(u32, Image)>>();
+    let cache = LayerCache::new(app_config.get_cache_size() * 1024 * 1024);
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(app_config.get_jobs())
+        .build()
+        .context(context)?;
+    log_info(format!(
+        "Process {} editions with {} jobs",
+        editions.len(),
+        pool.current_num_threads()
+    ));
+
+    let manifest = Manifest::open(
+        app_config.get_destination_dir(),
+        app_config.is_resume_enabled(),
+    )
+    .context(context)?;
+
+    let mut manifest_editions = pool
+        .install(|| {
+            editions
+                .par_iter()
+                .map(|(edition, composite)| {
+                    let is_animated = composite
+                        .save(*edition, app_config, edition_config, &cache)
+                        .context("save composite while image processing")?;
+                    let manifest_edition = ManifestEdition::from_image(
+                        *edition,
+                        composite,
+                        edition_config,
+                        is_animated,
+                    );
+                    manifest.append(&manifest_edition)?;
+
+                    Ok(manifest_edition)
+                })
+                .collect::<Result<Vec<ManifestEdition>>>()
+        })
+        .context(context)?;
+    cache.log_stats();
+
+    // one of ones of a resumed run are complete editions
+    let one_of_ones = one_of_ones
+        .into_iter()
+        .filter(|(edition, one_of_one)| {
+            complete_editions
+                .get(edition)
+                .is_none_or(|complete| complete.get_dna() != one_of_one.get_dna())
+        })
+        .collect::<Vec<(u32, OneOfOne)>>();
+    let one_of_one_editions = pool
+        .install(|| {
+            one_of_ones
+                .par_iter()
+                .map(|(edition, one_of_one)| {
+                    one_of_one
+                        .save(*edition, app_config, edition_config)
+                        .context("save one of one while image processing")?;
+                    let manifest_edition = ManifestEdition::from_one_of_one(*edition, one_of_one);
+                    manifest.append(&manifest_edition)?;
+
+                    Ok(manifest_edition)
+                })
+                .collect::<Result<Vec<ManifestEdition>>>()
+        })
+        .context(context)?;
+    drop(manifest);
+
+    manifest_editions.extend(one_of_one_editions);
+    manifest_editions.extend(complete_editions.into_values());
+    manifest_editions.sort_by_key(|e| e.get_edition());
+    save_manifest(app_config.get_destination_dir(), &manifest_editions).context(context)?;
+
+    let edition_traits = manifest_editions
+        .iter()
+        .map(|e| (e.get_edition(), e.get_traits().clone()))
+        .collect::<Vec<(u32, Vec<(String, String)>)>>();
+
+    RarityReport::new(layers, &edition_traits)
+        .save(app_config.get_destination_dir())
+        .context(context)?;
+    log_info("Saved rarity report (rarity.json, rarity.txt)");
+
+    let provenance =
+        ProvenanceRecord::new(app_config, edition_config.get_edition_size()).context(context)?;
+    provenance
+        .save(app_config.get_destination_dir())
+        .context(context)?;
+    log_info(format!(
+        "Saved provenance record ({}) with provenance hash {}",
+        PROVENANCE_FILE_NAME,
+        provenance.get_provenance_hash()
+    ));
+
+    if app_config.is_ipfs_enabled() {
+        save_ipfs(edition_config, app_config).context(context)?;
+        log_info(format!("Saved CIDs of the images ({})", IPFS_FILE_NAME));
+    } else {
+        save_collection_metadata(edition_config, app_config.get_destination_dir())
+            .context(context)?;
+    }
+    log_info(format!(
+        "Saved collection metadata ({}, {})",
+        METADATA_FILE_NAME, COLLECTION_FILE_NAME
+    ));
+    if edition_config.get_metadata().get_profile() == MetadataProfile::Cip25 {
+        log_info(format!(
+            "Saved combined CIP-25 policy map ({})",
+            CIP25_FILE_NAME
+        ));
+    }
+
+    Ok(())
+}
+
+/// Picks the layer files of all editions sequentially, so the uniqueness check and seeded
+/// runs stay deterministic, while the expensive compositing can happen in parallel afterwards.
+/// Already complete editions (of a resumed run) keep their number and DNA, the reserved numbers
+/// of the one of ones and pinned editions are skipped. Pinned editions count towards the size of
+/// their layer configuration and take part in the uniqueness check. A layer configuration which
+/// can't be filled within the retries is left with fewer editions, with trait quotas all editions
+/// are picked again with new quota assignments instead, until the run fails.
+fn pick_editions<L: AsRef<Path>, D: AsRef<Path>, R: Rng>(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    app_config: &AppConfiguration<L, D>,
+    complete_editions: &HashMap<u32, ManifestEdition>,
+    reserved: &HashSet<u32>,
+    pinned: &[(u32, Image)],
+    rng: &mut R,
+) -> Result<Vec<(u32, Image)>> {
+    let mut attempt = 1;
+
+    loop {
+        let (editions, issues) = pick_editions_attempt(
+            layers,
+            edition_config,
+            app_config,
+            complete_editions,
+            reserved,
+            pinned,
+            rng,
+        )?;
+
+        if issues.is_empty() {
+            return Ok(editions);
+        }
+        // without quotas, the editions which could be picked are created anyway
+        if edition_config.get_quotas().is_empty() {
+            for issue in issues {
+                log_warn(issue);
+            }
+
+            return Ok(editions);
+        }
+        if attempt >= QUOTA_ATTEMPTS {
+            bail!("{}", issues.join(", "));
+        }
+
+        attempt += 1;
+        log_warn(format!(
+            "{}, pick all editions again ({}/{})",
+            issues.join(", "),
+            attempt,
+            QUOTA_ATTEMPTS
+        ));
+    }
+}
+
+/// Picked editions and the reasons they aren't complete.
+type EditionsAttempt = (Vec<(u32, Image)>, Vec<String>);
+
+/// Picks all editions once, returns them along with the reasons they aren't complete, i.e. the
+/// layer configurations which couldn't be filled or the trait quotas which aren't met.
+fn pick_editions_attempt<L: AsRef<Path>, D: AsRef<Path>, R: Rng>(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    app_config: &AppConfiguration<L, D>,
+    complete_editions: &HashMap<u32, ManifestEdition>,
+    reserved: &HashSet<u32>,
+    pinned: &[(u32, Image)],
+    rng: &mut R,
+) -> Result<EditionsAttempt> {
+    // complete editions keep their number, so the numbers left can be in any order of the
+    // layer configurations after a shuffled run
+    let mut numbers = (1..=edition_config.get_edition_size())
+        .filter(|n| !reserved.contains(n) && !complete_editions.contains_key(n))
+        .collect::<Vec<u32>>()
+        .into_iter();
+    // one of ones don't take part in the uniqueness check of generated editions
+    let mut existing_dna: Vec<String> = complete_editions
+        .iter()
+        .filter(|(edition, _)| !reserved.contains(edition))
+        .map(|(_, e)| e.get_dna().to_string())
+        .chain(pinned.iter().map(|(_, image)| image.get_dna().to_string()))
+        .collect();
+    // complete and pinned editions count against the trait quotas
+    let mut quotas = Quotas::new(edition_config);
+    for (_, complete) in complete_editions
+        .iter()
+        .filter(|(edition, _)| !reserved.contains(edition))
+    {
+        quotas.take(&complete.get_layer_traits());
+    }
+    for (_, image) in pinned {
+        quotas.take(&image.get_layer_traits());
+    }
+    let edition_sizes = (0..edition_config.get_layers().len())
+        .map(|index| {
+            edition_config.get_layers()[index]
+                .get_size()
+                .saturating_sub(count_layer_config_editions(
+                    index,
+                    complete_editions,
+                    reserved,
+                    pinned,
+                ))
+        })
+        .collect::<Vec<u32>>();
+    let edition_layer_configs = edition_sizes
+        .iter()
+        .enumerate()
+        .flat_map(|(index, size)| (0..*size).map(move |_| index))
+        .collect::<Vec<usize>>();
+    let mut assignment = quotas.assign(edition_config, &edition_layer_configs, rng)?;
+    let mut editions = vec![];
+    let mut issues = vec![];
+    let max_tries = app_config.get_max_tries();
+
+    for (index, layer_config) in edition_config.get_layers().iter().enumerate() {
         let mut retries = 0;
-        edition_size += layer_config.get_size();
+        let mut edition_items = 0;
+        let first_slot = edition_sizes[..index].iter().sum::<u32>();
 
-        while edition_items < edition_size && retries < max_tries {
-            let composite = Image::from_layers(layers, layer_config);
+        while edition_items < edition_sizes[index] {
+            if retries >= max_tries {
+                issues.push(format!(
+                    "Layer configuration #{} is left with {} of {} editions after {} retries",
+                    index, edition_items, edition_sizes[index], retries
+                ));
+                break;
+            }
+
+            let slot = (first_slot + edition_items) as usize;
+            let composite =
+                match Image::from_layers(layers, edition_config, index, &assignment, slot, rng)? {
+                    Some(composite) => composite,
+                    None => {
+                        retries += 1;
+                        if is_retry_logged(retries) {
+                            log_warn(format!(
+                                "Requirements leave too few files to pick from!\t|\t Retry! ({})",
+                                retries
+                            ));
+                        }
+                        assignment.reassign(slot, rng);
+                        continue;
+                    }
+                };
             let composite_dna = composite.get_dna().to_string();
 
-            if existing_dna.contains(&composite_dna) {
+            if !quotas.allows(layer_config, &composite.get_layer_traits()) {
+                retries += 1;
+                if is_retry_logged(retries) {
+                    log_warn(format!(
+                        "Traits miss the assigned quota files! ({})\t|\t Retry! ({})",
+                        &composite_dna[..6],
+                        retries
+                    ));
+                }
+            } else if composite.violates_exclusions(edition_config) {
+                retries += 1;
+                if is_retry_logged(retries) {
+                    log_warn(format!(
+                        "Traits violate exclusion rules! ({})\t|\t Retry! ({})",
+                        &composite_dna[..6],
+                        retries
+                    ));
+                }
+            } else if existing_dna.contains(&composite_dna) {
                 retries += 1;
                 check_log_existing_dna(retries, &composite_dna);
             } else {
-                composite
-                    .save(edition_items + 1, app_config, edition_config)
-                    .context("save composite while image processing")?;
-
+                quotas.take(&composite.get_layer_traits());
                 existing_dna.push(composite_dna);
                 edition_items += 1;
+                let edition = numbers
+                    .next()
+                    .context("No edition number left for a generated edition")?;
+                editions.push((edition, composite));
+                continue;
             }
+            assignment.reassign(slot, rng);
         }
     }
 
-    Ok(())
+    // met by the assignment, unless an edition couldn't be picked
+    issues.append(&mut quotas.get_unmet());
+
+    Ok((editions, issues))
+}
+
+/// Reserves the edition numbers of the one of ones, either the configured number or a random one
+/// not taken by a pinned edition.
+/// One of ones of a resumed run keep their number, complete editions at a number taken by a one
+/// of one are dropped, so they get overwritten.
+fn place_one_of_ones<'a, R: Rng>(
+    edition_config: &'a EditionConfiguration,
+    complete_editions: &mut HashMap<u32, ManifestEdition>,
+    pinned: &HashSet<u32>,
+    rng: &mut R,
+) -> Result<Vec<(u32, OneOfOne<'a>)>> {
+    let one_of_ones = edition_config
+        .get_one_of_ones()
+        .iter()
+        .map(OneOfOne::try_from_config)
+        .collect::<Result<Vec<OneOfOne>>>()?;
+
+    let mut dna = HashSet::new();
+    for one_of_one in &one_of_ones {
+        if !dna.insert(one_of_one.get_dna()) {
+            bail!(
+                "One of one ({}) has the same image as another one",
+                one_of_one.get_config().get_image().display()
+            );
+        }
+    }
+
+    let mut placed = vec![];
+    let mut unplaced = vec![];
+    for one_of_one in one_of_ones {
+        match one_of_one.get_config().get_edition() {
+            Some(edition) => placed.push((edition, one_of_one)),
+            None => unplaced.push(one_of_one),
+        }
+    }
+    let mut reserved = placed
+        .iter()
+        .map(|(edition, _)| *edition)
+        .chain(pinned.iter().copied())
+        .collect::<HashSet<u32>>();
+
+    let mut random = vec![];
+    for one_of_one in unplaced {
+        let resumed = complete_editions
+            .iter()
+            .find(|(edition, complete)| {
+                !reserved.contains(edition) && complete.get_dna() == one_of_one.get_dna()
+            })
+            .map(|(edition, _)| *edition);
+
+        match resumed {
+            Some(edition) => {
+                reserved.insert(edition);
+                placed.push((edition, one_of_one));
+            }
+            None => random.push(one_of_one),
+        }
+    }
+
+    for one_of_one in random {
+        let free = (1..=edition_config.get_edition_size())
+            .filter(|n| !reserved.contains(n))
+            .collect::<Vec<u32>>();
+        // prefer numbers of missing editions over overwriting complete ones
+        let missing = free
+            .iter()
+            .copied()
+            .filter(|n| !complete_editions.contains_key(n))
+            .collect::<Vec<u32>>();
+        let edition = *missing
+            .choose(rng)
+            .or_else(|| free.choose(rng))
+            .context("No edition number left for a one of one")?;
+
+        reserved.insert(edition);
+        placed.push((edition, one_of_one));
+    }
+
+    for (edition, one_of_one) in &placed {
+        if complete_editions
+            .get(edition)
+            .is_some_and(|complete| complete.get_dna() != one_of_one.get_dna())
+        {
+            log_warn(format!(
+                "Edition #{} is replaced by one of one ({})",
+                edition,
+                one_of_one.get_config().get_image().display()
+            ));
+            complete_editions.remove(edition);
+        }
+    }
+    placed.sort_by_key(|(edition, _)| *edition);
+
+    Ok(placed)
+}
+
+/// Drops complete editions (of a resumed run) which don't fit the edition configuration anymore,
+/// i.e. numbers beyond the edition size, one of ones which aren't configured anymore and editions
+/// exceeding the size of their layer configuration, so they get generated again.
+fn drop_unassigned_editions(
+    edition_config: &EditionConfiguration,
+    complete_editions: &mut HashMap<u32, ManifestEdition>,
+    reserved: &HashSet<u32>,
+    pinned: &[(u32, Image)],
+) {
+    let layer_configs = edition_config.get_layers();
+    let mut sizes = vec![0; layer_configs.len()];
+    for (_, image) in pinned {
+        sizes[image.get_layer_config()] += 1;
+    }
+
+    let mut numbers = complete_editions
+        .keys()
+        .copied()
+        .filter(|edition| !reserved.contains(edition))
+        .collect::<Vec<u32>>();
+    numbers.sort_unstable();
+
+    for edition in numbers {
+        let fits = edition <= edition_config.get_edition_size()
+            && match complete_editions[&edition].get_layer_config() {
+                Some(index) if index < layer_configs.len() => {
+                    sizes[index] += 1;
+                    sizes[index] <= layer_configs[index].get_size()
+                }
+                _ => false,
+            };
+
+        if !fits {
+            log_warn(format!(
+                "Edition #{} doesn't fit the edition configuration anymore and is generated again",
+                edition
+            ));
+            complete_editions.remove(&edition);
+        }
+    }
+}
+
+/// Counts the complete and pinned editions of the layer configuration, complete editions are
+/// counted by their recorded layer configuration, not by their number.
+fn count_layer_config_editions(
+    index: usize,
+    complete_editions: &HashMap<u32, ManifestEdition>,
+    reserved: &HashSet<u32>,
+    pinned: &[(u32, Image)],
+) -> u32 {
+    let complete = complete_editions
+        .iter()
+        .filter(|(edition, complete)| {
+            !reserved.contains(edition) && complete.get_layer_config() == Some(index)
+        })
+        .count();
+    let pinned = pinned
+        .iter()
+        .filter(|(_, image)| image.get_layer_config() == index)
+        .count();
+
+    (complete + pinned) as u32
+}
+
+/// Permutes the numbers of the picked editions, so the number of an edition doesn't reveal its
+/// layer configuration. Complete editions of a resumed run keep their number.
+fn shuffle_editions<R: Rng>(editions: &mut [(u32, Image)], rng: &mut R) {
+    let mut numbers = editions
+        .iter()
+        .map(|(edition, _)| *edition)
+        .collect::<Vec<u32>>();
+    numbers.shuffle(rng);
+
+    for ((edition, _), number) in editions.iter_mut().zip(numbers) {
+        *edition = number;
+    }
+    editions.sort_by_key(|(edition, _)| *edition);
+}
+
+/// Locks state shared by the workers, a poisoned lock only means another worker panicked, the
+/// state itself stays valid.
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn check_capacity(layers: &Layers, edition_config: &EditionConfiguration) {
+    for layer_config in edition_config.get_layers() {
+        let capacity = capacity(layers, edition_config, layer_config);
+
+        if capacity.is_exceeded() {
+            log_warn(format!(
+                "{}, not all editions can be created",
+                capacity.describe()
+            ));
+        } else if capacity.is_critical() {
+            log_warn(format!(
+                "{}, expect a lot of duplicate retries",
+                capacity.describe()
+            ));
+        }
+    }
 }
 
 fn check_log_existing_dna(retries: u32, composite_dna: &str) {
-    if retries < 1000 {
+    if is_retry_logged(retries) {
         log_existing_dna(retries, composite_dna);
-        return;
     }
+}
 
-    if retries < 3000 && retries % 100 == 0 {
-        log_existing_dna(retries, composite_dna);
-        return;
+fn is_retry_logged(retries: u32) -> bool {
+    if retries < 1000 {
+        return true;
     }
 
-    if retries < 5000 && retries % 250 == 0 {
-        log_existing_dna(retries, composite_dna);
-        return;
+    if retries < 3000 {
+        return retries.is_multiple_of(100);
     }
 
-    if retries % 500 == 0 {
-        log_existing_dna(retries, composite_dna);
+    if retries < 5000 {
+        return retries.is_multiple_of(250);
     }
+
+    retries.is_multiple_of(500)
 }
 
 fn log_existing_dna(retries: u32, composite_dna: &str) {
@@ -81,3 +657,226 @@ fn log_existing_dna(retries: u32, composite_dna: &str) {
         retries
     ));
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, remove_file};
+    use std::path::PathBuf;
+
+    use image::{ImageBuffer, Rgba};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::processor::manifest::read_manifest;
+    use crate::test_utils::{create_test_images, test_config, test_edition_config, TempDir};
+
+    const LAYERS: [(&str, &[&str]); 3] = [
+        ("Background", &["Blue", "Green", "Red"]),
+        ("Hat", &["Beanie", "Cap", "Crown"]),
+        ("Eyes", &["Big", "Small", "Wide"]),
+    ];
+
+    /// Checks the name, attributes and top layer color of every edition against its manifest
+    /// record, returns the number of editions per layer configuration.
+    fn check_editions(destination: &Path) -> Vec<usize> {
+        let manifest = read_manifest(destination).unwrap();
+        let mut counts = vec![0; 2];
+
+        for edition in 1..=8 {
+            let record = &manifest[&edition];
+            let layer_config = record.get_layer_config().unwrap();
+            counts[layer_config] += 1;
+
+            let meta: Value = serde_json::from_str(
+                &read_to_string(destination.join("meta").join(format!("{}.json", edition)))
+                    .unwrap(),
+            )
+            .unwrap();
+            let name = ["Day", "Night"][layer_config];
+            assert_eq!(meta["name"], format!("{} #{}", name, edition));
+            let attributes = meta["attributes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|a| {
+                    let trait_type = a["trait_type"].as_str().unwrap().to_string();
+                    (trait_type, a["value"].as_str().unwrap().to_string())
+                })
+                .collect::<Vec<(String, String)>>();
+            assert_eq!(&attributes, record.get_traits());
+
+            // the files of the top layer cover the whole image
+            let (layer, file) = *record.get_layer_traits().last().unwrap();
+            let layer_index = LAYERS.iter().position(|(l, _)| *l == layer).unwrap();
+            let file_index = LAYERS[layer_index]
+                .1
+                .iter()
+                .position(|f| *f == file)
+                .unwrap();
+            let image = image::open(destination.join("images").join(format!("{}.png", edition)))
+                .unwrap()
+                .to_rgba8();
+            assert_eq!(
+                *image.get_pixel(0, 0),
+                Rgba([layer_index as u8 * 40, file_index as u8 * 40, 100, 255])
+            );
+        }
+
+        counts
+    }
+
+    #[test]
+    fn shuffled_editions_stay_consistent_on_resume() {
+        let dir = TempDir::with_layers("shuffle", &LAYERS);
+        let config = test_config(json!({
+            "shuffleEditions": true,
+            "layers": [
+                {"size": 4, "name": "Day", "order": [{"name": "Background"}, {"name": "Hat"}]},
+                {"size": 4, "name": "Night", "order": [{"name": "Background"}, {"name": "Eyes"}]}
+            ]
+        }));
+        create_test_images(&dir, &config, &[], 3).unwrap();
+        let out = dir.join("out");
+        assert_eq!(check_editions(&out), vec![4, 4]);
+
+        // regenerate the highest numbers, which belong to both layer configurations when shuffled
+        let manifest = read_manifest(&out).unwrap();
+        for edition in 5..=8 {
+            remove_file(out.join("images").join(format!("{}.png", edition))).unwrap();
+        }
+
+        create_test_images(&dir, &config, &["--resume"], 4).unwrap();
+        assert_eq!(check_editions(&out), vec![4, 4]);
+        let resumed = read_manifest(&out).unwrap();
+        for edition in 1..=4 {
+            assert_eq!(resumed[&edition].get_dna(), manifest[&edition].get_dna());
+        }
+    }
+
+    fn write_one_of_ones(dir: &TempDir, count: u8) -> Vec<PathBuf> {
+        (0..count)
+            .map(|i| {
+                let path = dir.join(format!("one_{}.png", i));
+                ImageBuffer::from_pixel(4, 4, Rgba([255, i * 40, 0, 255]))
+                    .save(&path)
+                    .unwrap();
+
+                path
+            })
+            .collect()
+    }
+
+    fn manifest_edition(edition: u32, dna: &str) -> ManifestEdition {
+        serde_json::from_value(json!({"edition": edition, "layerConfiguration": 0, "dna": dna}))
+            .unwrap()
+    }
+
+    #[test]
+    fn one_of_ones_are_placed_around_reserved_and_resumed_editions() {
+        let dir = TempDir::new("place_one_of_ones");
+        let images = write_one_of_ones(&dir, 3);
+        let edition_config = test_edition_config(json!({
+            "layers": [{"size": 3, "order": []}],
+            "oneOfOnes": [{"image": images[0], "edition": 2}, {"image": images[1]}, {"image": images[2]}]
+        }));
+        let resumed_dna = OneOfOne::try_from_config(&edition_config.get_one_of_ones()[1])
+            .unwrap()
+            .get_dna()
+            .to_string();
+
+        // a generated edition at the fixed number, the random one of one of a resumed run
+        let mut complete_editions = HashMap::new();
+        complete_editions.insert(1, manifest_edition(1, "a"));
+        complete_editions.insert(2, manifest_edition(2, "b"));
+        complete_editions.insert(5, manifest_edition(5, &resumed_dna));
+        let pinned = HashSet::from([4]);
+
+        let placed = place_one_of_ones(
+            &edition_config,
+            &mut complete_editions,
+            &pinned,
+            &mut ChaCha20Rng::seed_from_u64(1),
+        )
+        .unwrap();
+
+        let numbers = placed
+            .iter()
+            .map(|(edition, one_of_one)| (one_of_one.get_config().get_image(), *edition))
+            .collect::<HashMap<&Path, u32>>();
+        assert_eq!(numbers[images[0].as_path()], 2);
+        assert_eq!(numbers[images[1].as_path()], 5);
+        // missing numbers are preferred over complete editions
+        assert!([3, 6].contains(&numbers[images[2].as_path()]));
+        let mut complete = complete_editions.keys().copied().collect::<Vec<u32>>();
+        complete.sort();
+        assert_eq!(complete, vec![1, 5]);
+    }
+
+    #[test]
+    fn resumed_one_of_ones_keep_their_number() {
+        let dir = TempDir::with_layers("resume_one_of_ones", &LAYERS[..2]);
+        let images = write_one_of_ones(&dir, 1);
+        let config = test_config(json!({
+            "layers": [{"size": 5, "order": [{"name": "Background"}, {"name": "Hat"}]}],
+            "oneOfOnes": [{"image": images[0], "name": "The One"}]
+        }));
+        let one_of_one_number = |destination: &Path| {
+            let manifest = read_manifest(destination).unwrap();
+            assert_eq!(manifest.len(), 6);
+            let numbers = manifest
+                .values()
+                .filter(|e| e.get_layer_config().is_none())
+                .map(|e| e.get_edition())
+                .collect::<Vec<u32>>();
+            assert_eq!(numbers.len(), 1);
+
+            numbers[0]
+        };
+        create_test_images(&dir, &config, &[], 5).unwrap();
+        let out = dir.join("out");
+        let number = one_of_one_number(&out);
+
+        // remove all generated editions, so a new random pick could take any number
+        for edition in (1..=6).filter(|edition| *edition != number) {
+            remove_file(out.join("images").join(format!("{}.png", edition))).unwrap();
+        }
+        create_test_images(&dir, &config, &["--resume"], 6).unwrap();
+
+        assert_eq!(one_of_one_number(&out), number);
+        let meta: Value = serde_json::from_str(
+            &read_to_string(out.join("meta").join(format!("{}.json", number))).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(meta["name"], "The One");
+        let image = image::open(out.join("images").join(format!("{}.png", number)))
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(*image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn unfilled_layer_configuration_keeps_its_editions_without_quotas() {
+        let dir = TempDir::with_layers("unfilled", &LAYERS);
+        // three backgrounds can't fill four unique editions
+        let config = test_config(json!({
+            "layers": [
+                {"size": 4, "order": [{"name": "Background"}]},
+                {"size": 2, "order": [{"name": "Background"}, {"name": "Hat"}]}
+            ]
+        }));
+        create_test_images(&dir, &config, &["-m", "20"], 7).unwrap();
+
+        let out = dir.join("out");
+        let manifest = read_manifest(&out).unwrap();
+        let mut counts = vec![0; 2];
+        for edition in manifest.values() {
+            counts[edition.get_layer_config().unwrap()] += 1;
+        }
+        assert_eq!(counts, vec![3, 2]);
+        let metas: Vec<Value> =
+            serde_json::from_str(&read_to_string(out.join(METADATA_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(metas.len(), 5);
+    }
+}
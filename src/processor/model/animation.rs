@@ -0,0 +1,187 @@
+use crate::config::output::{AnimationConfiguration, FrameStrategy};
+use crate::logger::log_warn;
+
+/// Frame of the composite animation, pointing to the frame of every layer shown meanwhile.
+#[derive(Debug, PartialEq)]
+pub(super) struct TimelineFrame {
+    indices: Vec<usize>,
+    delay: u32,
+}
+
+impl TimelineFrame {
+    pub(super) fn get_indices(&self) -> &Vec<usize> {
+        &self.indices
+    }
+
+    pub(super) fn get_delay(&self) -> u32 {
+        self.delay
+    }
+}
+
+/// Combines the frame delays of all layers into the frames of the composite animation. Layers
+/// with a single frame are shown during the whole animation, without any animated layer the
+/// result is a single frame.
+pub(super) fn reconcile(
+    layer_delays: &[&[u32]],
+    animation: &AnimationConfiguration,
+) -> Vec<TimelineFrame> {
+    let delays = layer_delays
+        .iter()
+        .map(|delays| {
+            delays
+                .iter()
+                .map(|d| {
+                    if *d == 0 {
+                        animation.get_frame_delay()
+                    } else {
+                        *d
+                    }
+                })
+                .collect::<Vec<u32>>()
+        })
+        .collect::<Vec<Vec<u32>>>();
+
+    if delays.iter().all(|d| d.len() <= 1) {
+        return vec![TimelineFrame {
+            indices: vec![0; delays.len()],
+            delay: 0,
+        }];
+    }
+
+    let (frames, is_complete) = match animation.get_strategy() {
+        FrameStrategy::Timeline => timeline_frames(&delays, animation.get_max_frames()),
+        FrameStrategy::Longest => longest_frames(&delays, animation.get_max_frames()),
+    };
+    if !is_complete {
+        log_warn(format!(
+            "Animation is cut off after {} frames, increase 'max-frames' to keep all of them",
+            frames.len()
+        ));
+    }
+
+    frames
+}
+
+/// Walks the timeline from one frame change of any layer to the next, until every layer loop
+/// ends at the same time (the least common multiple of the loop durations).
+fn timeline_frames(delays: &[Vec<u32>], max_frames: usize) -> (Vec<TimelineFrame>, bool) {
+    let is_animated = |d: &Vec<u32>| d.len() > 1;
+    let total = delays
+        .iter()
+        .filter(|d| is_animated(d))
+        .map(|d| d.iter().map(|d| *d as u64).sum::<u64>())
+        .fold(1u64, |total, duration| {
+            (total / gcd(total, duration)).saturating_mul(duration)
+        });
+
+    let mut indices = vec![0usize; delays.len()];
+    let mut frame_ends = delays
+        .iter()
+        .map(|d| {
+            if is_animated(d) {
+                d[0] as u64
+            } else {
+                u64::MAX
+            }
+        })
+        .collect::<Vec<u64>>();
+    let mut time = 0u64;
+    let mut frames = vec![];
+
+    while time < total {
+        if frames.len() == max_frames {
+            return (frames, false);
+        }
+
+        let next = *frame_ends.iter().min().unwrap_or(&total);
+        frames.push(TimelineFrame {
+            indices: indices.clone(),
+            delay: (next - time) as u32,
+        });
+        time = next;
+
+        for (layer, frame_end) in frame_ends.iter_mut().enumerate() {
+            if *frame_end == time {
+                indices[layer] = (indices[layer] + 1) % delays[layer].len();
+                *frame_end += delays[layer][indices[layer]] as u64;
+            }
+        }
+    }
+
+    (frames, true)
+}
+
+/// Plays the frames of the layer with the most frames, the other layers advance one frame each
+/// time and loop.
+fn longest_frames(delays: &[Vec<u32>], max_frames: usize) -> (Vec<TimelineFrame>, bool) {
+    let longest = delays
+        .iter()
+        .max_by_key(|d| d.len())
+        .cloned()
+        .unwrap_or_default();
+
+    let frames = (0..longest.len().min(max_frames))
+        .map(|frame| TimelineFrame {
+            indices: delays.iter().map(|d| frame % d.len()).collect(),
+            delay: longest[frame],
+        })
+        .collect();
+
+    (frames, longest.len() <= max_frames)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(timeline: &[TimelineFrame]) -> Vec<(Vec<usize>, u32)> {
+        timeline
+            .iter()
+            .map(|f| (f.indices.clone(), f.delay))
+            .collect()
+    }
+
+    #[test]
+    fn timeline_loops_until_all_layers_end() {
+        let delays = vec![vec![0], vec![100, 100], vec![50, 100]];
+        let (timeline, is_complete) = timeline_frames(&delays, 100);
+
+        assert!(is_complete);
+        assert_eq!(
+            frames(&timeline),
+            vec![
+                (vec![0, 0, 0], 50),
+                (vec![0, 0, 1], 50),
+                (vec![0, 1, 1], 50),
+                (vec![0, 1, 0], 50),
+                (vec![0, 0, 1], 100),
+                (vec![0, 1, 0], 50),
+                (vec![0, 1, 1], 50),
+                (vec![0, 0, 1], 50),
+                (vec![0, 0, 0], 50),
+                (vec![0, 1, 1], 100),
+            ]
+        );
+        assert_eq!(timeline.iter().map(|f| f.delay).sum::<u32>(), 600);
+    }
+
+    #[test]
+    fn longest_loops_shorter_layers() {
+        let delays = vec![vec![80, 80], vec![100, 120, 140]];
+        let (timeline, is_complete) = longest_frames(&delays, 2);
+
+        assert!(!is_complete);
+        assert_eq!(
+            frames(&timeline),
+            vec![(vec![0, 0], 100), (vec![1, 1], 120)]
+        );
+    }
+}
@@ -1,75 +1,287 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::fs::{create_dir_all, write};
+use std::fs::{create_dir_all, rename, write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use image::imageops::overlay;
-use image::{open, ImageBuffer, Rgba};
+use image::imageops::{overlay, resize, FilterType};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rand::Rng;
+use serde_json::Value;
 
 use crate::config::app::AppConfiguration;
-use crate::config::edition::{EditionConfiguration, LayerConfiguration};
+use crate::config::edition::{
+    BlendMode, EditionConfiguration, LayerOrderConfiguration, TransformConfiguration,
+};
+use crate::config::output::AnimationConfiguration;
 use crate::hashing::simple_sha256;
-use crate::layers_model::{Layers, RngLayerFile};
+use crate::layers_model::{split_rarity_weight, LayerFrames, Layers, RngLayerFile};
 use crate::logger::log_info;
+use crate::processor::cache::LayerCache;
+use crate::processor::encoder::{encode, encode_animation};
+use crate::processor::model::animation::reconcile;
 use crate::processor::model::meta::Meta;
+use crate::processor::model::transform::{position, transform_image};
+use crate::processor::quota::QuotaAssignment;
 
 #[derive(Debug)]
 pub(in super::super) struct Image {
     files: Vec<ImageFile>,
     dna: String,
+    /// Index of the layer configuration the image was picked from.
+    layer_config: usize,
 }
 
 impl Image {
-    pub(in super::super) fn from_layers(
+    /// Picks the layer files of an image with the quota files assigned to the slot, `None` if the
+    /// 'requires' rules of a layer leave less files than it has to pick, so the pick is rejected.
+    pub(in super::super) fn from_layers<R: Rng>(
         layers: &Layers,
-        layer_config: &LayerConfiguration,
-    ) -> Image {
-        let mut composite_files = vec![];
-        layer_config.get_order().iter().for_each(|lo| {
-            composite_files.append(&mut layers.get_rng_files(
-                lo.get_name(),
-                lo.get_pick_min(),
-                lo.get_pick_max(),
-            ))
-        });
+        edition_config: &EditionConfiguration,
+        layer_config_index: usize,
+        quotas: &QuotaAssignment,
+        slot: usize,
+        rng: &mut R,
+    ) -> Result<Option<Image>> {
+        let layer_config = &edition_config.get_layers()[layer_config_index];
+        let mut composite_files: Vec<(RngLayerFile, &LayerOrderConfiguration)> = vec![];
+
+        for lo in layer_config.get_order() {
+            let picked = || {
+                composite_files
+                    .iter()
+                    .map(|(f, _)| (f.get_layer(), f.get_name()))
+            };
+
+            let only_if = lo.get_only_if();
+            if !only_if.is_empty()
+                && !only_if
+                    .iter()
+                    .any(|s| picked().any(|(layer, name)| s.matches(layer, name)))
+            {
+                continue;
+            }
+
+            let is_allowed =
+                |name: &str| lo.get_requires().iter().all(|r| r.allows(picked(), name));
+            // quota layers are picked exactly once, editions missing them are rejected
+            let is_quota_layer = quotas.has_layer(lo.get_name());
+            let files = if is_quota_layer {
+                layers
+                    .get_weighted_file(lo.get_name(), rng, |name, weight| {
+                        if is_allowed(name) {
+                            quotas.weight(slot, lo.get_name(), name, weight)
+                        } else {
+                            0.0
+                        }
+                    })
+                    .into_iter()
+                    .collect()
+            } else {
+                layers.get_rng_files(
+                    lo.get_name(),
+                    lo.get_pick_min(),
+                    lo.get_pick_max(),
+                    rng,
+                    is_allowed,
+                )
+            };
+
+            let is_restricted = lo
+                .get_requires()
+                .iter()
+                .any(|r| picked().any(|(layer, name)| r.get_selector().matches(layer, name)));
+            if is_restricted && !is_quota_layer && (files.len() as u32) < lo.get_pick_min() {
+                return Ok(None);
+            }
+
+            composite_files.extend(files.into_iter().map(|f| (f, lo)));
+        }
+
+        Image::from_rng_files(&composite_files, layer_config_index).map(Some)
+    }
+
+    /// Image of the pinned file names (by layer), which have to follow the layer order and rules
+    /// of the layer configuration.
+    pub(in super::super) fn from_traits(
+        layers: &Layers,
+        edition_config: &EditionConfiguration,
+        layer_config_index: usize,
+        traits: &HashMap<&str, Vec<&str>>,
+    ) -> Result<Image> {
+        let layer_config = edition_config
+            .get_layers()
+            .get(layer_config_index)
+            .context(format!(
+                "Layer configuration #{} doesn't exist",
+                layer_config_index
+            ))?;
+        if let Some(layer) = traits.keys().find(|layer| {
+            !layer_config
+                .get_order()
+                .iter()
+                .any(|lo| lo.get_name() == **layer)
+        }) {
+            bail!(
+                "Layer ({}) isn't part of layer configuration #{}",
+                layer,
+                layer_config_index
+            );
+        }
 
-        Image::from_rng_files(&composite_files)
+        let mut composite_files: Vec<(RngLayerFile, &LayerOrderConfiguration)> = vec![];
+        for lo in layer_config.get_order() {
+            let picked = || {
+                composite_files
+                    .iter()
+                    .map(|(f, _)| (f.get_layer(), f.get_name()))
+            };
+            let names = traits.get(lo.get_name()).cloned().unwrap_or_default();
+
+            let only_if = lo.get_only_if();
+            if !only_if.is_empty()
+                && !only_if
+                    .iter()
+                    .any(|s| picked().any(|(layer, name)| s.matches(layer, name)))
+            {
+                if !names.is_empty() {
+                    bail!(
+                        "Layer ({}) is only drawn with one of its 'onlyIf' traits",
+                        lo.get_name()
+                    );
+                }
+                continue;
+            }
+
+            let count = names.len() as u32;
+            if count < lo.get_pick_min() || count > lo.get_pick_max() {
+                bail!(
+                    "Layer ({}) picks {} - {} files, got {}",
+                    lo.get_name(),
+                    lo.get_pick_min(),
+                    lo.get_pick_max(),
+                    count
+                );
+            }
+            if let Some(name) = names
+                .iter()
+                .enumerate()
+                .find(|(i, name)| names[..*i].contains(name))
+                .map(|(_, name)| name)
+            {
+                bail!(
+                    "File ({}) of layer ({}) is pinned twice",
+                    name,
+                    lo.get_name()
+                );
+            }
+            if let Some(name) = names
+                .iter()
+                .find(|name| !lo.get_requires().iter().all(|r| r.allows(picked(), name)))
+            {
+                bail!(
+                    "File ({}) of layer ({}) violates the 'requires' rules",
+                    name,
+                    lo.get_name()
+                );
+            }
+
+            let files = layers.get_files(lo.get_name(), &names)?;
+            composite_files.extend(files.into_iter().map(|f| (f, lo)));
+        }
+
+        let image = Image::from_rng_files(&composite_files, layer_config_index)?;
+        if image.violates_exclusions(edition_config) {
+            bail!("Traits violate the exclusion rules");
+        }
+
+        Ok(image)
     }
 
     pub(in super::super) fn get_dna(&self) -> &str {
         &self.dna
     }
 
+    /// Layer and file name pairs of the picked layer files.
+    pub(in super::super) fn get_layer_traits(&self) -> Vec<(&str, &str)> {
+        self.files
+            .iter()
+            .map(|f| (f.layer.as_str(), f.name.as_str()))
+            .collect()
+    }
+
+    /// Index of the layer configuration the image was picked from.
+    pub(in super::super) fn get_layer_config(&self) -> usize {
+        self.layer_config
+    }
+
+    /// Trait type (layer) and value (file name) pairs of the image, including the static
+    /// attributes of its layer configuration, like they appear in the metadata.
+    pub(in super::super) fn get_traits(
+        &self,
+        edition_config: &EditionConfiguration,
+    ) -> Vec<(String, String)> {
+        let mut traits = self
+            .files
+            .iter()
+            .map(|f| (f.layer.to_string(), f.name.to_string()))
+            .collect::<Vec<(String, String)>>();
+
+        for attribute in edition_config.get_layers()[self.layer_config].get_attributes() {
+            traits.retain(|(trait_type, _)| trait_type != attribute.get_trait_type());
+            traits.push((
+                attribute.get_trait_type().to_string(),
+                attribute.get_value_string(),
+            ));
+        }
+
+        traits
+    }
+
+    pub(in super::super) fn violates_exclusions(
+        &self,
+        edition_config: &EditionConfiguration,
+    ) -> bool {
+        self.files.iter().enumerate().any(|(i, file)| {
+            self.files[i + 1..].iter().any(|other| {
+                edition_config.is_excluded(&file.layer, &file.name, &other.layer, &other.name)
+            })
+        })
+    }
+
+    /// Saves the image and its metadata, returns whether an animation was saved.
     pub(in super::super) fn save<L: AsRef<Path>, D: AsRef<Path>>(
         &self,
         edition: u32,
         app_config: &AppConfiguration<L, D>,
         edition_config: &EditionConfiguration,
-    ) -> Result<()> {
+        cache: &LayerCache,
+    ) -> Result<bool> {
         let context = "Save image composite";
 
-        self.save_image(
-            edition,
-            app_config.get_size(),
-            app_config.get_destination_dir(),
-        )
-        .context(context)?;
-        self.save_meta(edition, edition_config, app_config.get_destination_dir())
+        let is_animated = self
+            .save_image(edition, app_config, cache)
+            .context(context)?;
+        self.save_meta(edition, edition_config, app_config, is_animated)
             .context(context)?;
 
         log_info(format!("Saved image and metadata #{}", edition,));
 
-        Ok(())
+        Ok(is_animated)
     }
 }
 
 impl Image {
-    fn from_rng_files(files: &[RngLayerFile]) -> Image {
+    fn from_rng_files(
+        files: &[(RngLayerFile, &LayerOrderConfiguration)],
+        layer_config: usize,
+    ) -> Result<Image> {
         let composite_files = files
             .iter()
-            .map(|f| ImageFile::try_from_path(f.get_layer(), f.get_path()))
-            .filter_map(|r| r.ok())
-            .collect::<Vec<ImageFile>>();
+            .map(|(f, lo)| ImageFile::try_from_rng_file(f, lo))
+            .collect::<Result<Vec<ImageFile>>>()
+            .context("Create image from picked layer files")?;
 
         let dna_string = composite_files
             .iter()
@@ -78,62 +290,99 @@ impl Image {
             .join("__");
         let dna = simple_sha256(dna_string.as_bytes());
 
-        Image {
+        Ok(Image {
             files: composite_files,
             dna,
-        }
+            layer_config,
+        })
     }
 
-    fn save_image<P: AsRef<Path>>(
+    /// Saves the image (and the animation, if any layer is animated), returns whether an animation
+    /// was saved.
+    fn save_image<L: AsRef<Path>, D: AsRef<Path>>(
         &self,
         edition: u32,
-        size: u32,
-        destination_path: P,
-    ) -> Result<()> {
+        app_config: &AppConfiguration<L, D>,
+        cache: &LayerCache,
+    ) -> Result<bool> {
+        let destination_path = app_config.get_destination_dir();
         let context = format!(
             "Save edition ({}) image at ({})",
             edition,
-            destination_path.as_ref().display()
+            destination_path.display()
         );
 
-        let image_paths = self
-            .files
-            .iter()
-            .map(|f| f.path.as_path())
-            .collect::<Vec<&Path>>();
+        let frames = composite_frames(
+            app_config.get_dimensions(),
+            app_config.get_filter(),
+            app_config.get_animation(),
+            &self.files,
+            cache,
+        )
+        .context(context.clone())?;
+        // the first frame is the still image of animated editions
+        let final_image = &frames[0].0;
+        let format = app_config.get_format();
+        save_file(
+            destination_path.join("images"),
+            edition,
+            format.get_extension(),
+            &encode(final_image, format).context(context.clone())?,
+        )
+        .context(context.clone())?;
+
+        // extra sizes are resampled from the composite, rather than compositing each size again
+        for (width, height) in app_config.get_extra_sizes() {
+            let extra_image = resize(final_image, *width, *height, app_config.get_filter());
+            save_file(
+                destination_path.join(extra_images_dir_name((*width, *height))),
+                edition,
+                format.get_extension(),
+                &encode(&extra_image, format).context(context.clone())?,
+            )
+            .context(context.clone())?;
+        }
 
-        let final_image = overlay_images(size, &image_paths).context(context.clone())?;
-        let destination = destination_path.as_ref().join("images");
-        if !destination.is_dir() {
-            create_dir_all(&destination).context(context.clone())?;
+        if frames.len() == 1 {
+            return Ok(false);
         }
-        let destination = destination.join(format!("{}.png", edition));
-        final_image.save(&destination).context(context)?;
 
-        Ok(())
+        let animation_format = app_config.get_animation().get_format();
+        save_file(
+            destination_path.join(ANIMATIONS_DIR_NAME),
+            edition,
+            animation_format.get_extension(),
+            &encode_animation(&frames, animation_format).context(context.clone())?,
+        )
+        .context(context)?;
+
+        Ok(true)
     }
 
-    fn save_meta<P: AsRef<Path>>(
+    fn save_meta<L: AsRef<Path>, D: AsRef<Path>>(
         &self,
         edition: u32,
         edition_config: &EditionConfiguration,
-        destination_path: P,
+        app_config: &AppConfiguration<L, D>,
+        is_animated: bool,
     ) -> Result<()> {
+        let destination_path = app_config.get_destination_dir();
         let context = format!(
             "Save edition ({}) meta at ({})",
             edition,
-            destination_path.as_ref().display()
+            destination_path.display()
         );
 
-        let meta = Meta::new(edition, edition_config, &self.files);
-        let meta_string = serde_json::to_string_pretty(&meta).context(context.clone())?;
-
-        let destination = destination_path.as_ref().join("meta");
-        if !destination.is_dir() {
-            create_dir_all(&destination).context(context.clone())?;
-        }
-        let destination = destination.join(format!("{}.json", edition));
-        write(&destination, meta_string).context(context)?;
+        let meta = Meta::new(
+            edition,
+            edition_config,
+            &edition_config.get_layers()[self.layer_config],
+            &self.files,
+            app_config.get_format().get_extension(),
+            is_animated.then(|| app_config.get_animation().get_format().get_extension()),
+        );
+        let meta = meta.to_value().context(context.clone())?;
+        save_meta_file(destination_path, edition, &meta).context(context)?;
 
         Ok(())
     }
@@ -145,6 +394,9 @@ pub(super) struct ImageFile {
     name: String,
     layer: String,
     path: PathBuf,
+    blend: BlendMode,
+    opacity: f32,
+    transform: Option<TransformConfiguration>,
 }
 
 impl ImageFile {
@@ -157,6 +409,22 @@ impl ImageFile {
 }
 
 impl ImageFile {
+    fn try_from_rng_file(
+        rng_file: &RngLayerFile,
+        layer_order: &LayerOrderConfiguration,
+    ) -> Result<ImageFile> {
+        let mut image_file = ImageFile::try_from_path(rng_file.get_layer(), rng_file.get_path())?;
+        image_file.blend = layer_order.get_blend();
+        image_file.opacity = layer_order.get_opacity();
+        // a sidecar transform of the file takes precedence over the transform of the layer
+        image_file.transform = rng_file
+            .get_transform()
+            .or_else(|| layer_order.get_transform())
+            .cloned();
+
+        Ok(image_file)
+    }
+
     fn try_from_path(layer: &str, path: &Path) -> Result<ImageFile> {
         let context = format!(
             "try to create image composite file for layer ({}) from path ({})",
@@ -166,17 +434,69 @@ impl ImageFile {
 
         let file_name = try_convert_os_str_to_string(path.file_name().context(context.clone())?)
             .context(context.clone())?;
-        let name = file_name.split('.').next().context(context)?.to_string();
+        let name = file_name.split('.').next().context(context)?;
+        let name = split_rarity_weight(name).0.to_string();
 
         Ok(ImageFile {
             _file_name: file_name,
             name,
             layer: layer.to_string(),
             path: path.to_path_buf(),
+            blend: BlendMode::Normal,
+            opacity: 1.0,
+            transform: None,
         })
     }
 }
 
+/// Name of the directory containing the animations of editions with animated layers.
+pub(in super::super) const ANIMATIONS_DIR_NAME: &str = "animations";
+
+/// Name of the directory containing the images of an extra size, next to the `images` directory.
+pub(in super::super) fn extra_images_dir_name((width, height): (u32, u32)) -> String {
+    format!("images_{}x{}", width, height)
+}
+
+/// Writes the encoded image to a temporary file first, so an interrupted run never leaves a
+/// truncated image behind.
+pub(super) fn save_file<P: AsRef<Path>>(
+    destination_dir: P,
+    edition: u32,
+    extension: &str,
+    bytes: &[u8],
+) -> Result<()> {
+    let destination = destination_dir.as_ref();
+    if !destination.is_dir() {
+        create_dir_all(destination)?;
+    }
+    let destination = destination.join(format!("{}.{}", edition, extension));
+    let temp_destination = destination.with_extension(format!("{}.tmp", extension));
+    write(&temp_destination, bytes)?;
+    rename(&temp_destination, &destination)?;
+
+    Ok(())
+}
+
+/// Writes the metadata of the edition to a temporary file first, like the images.
+pub(super) fn save_meta_file<P: AsRef<Path>>(
+    destination_path: P,
+    edition: u32,
+    meta: &Value,
+) -> Result<()> {
+    let meta_string = serde_json::to_string_pretty(meta)?;
+
+    let destination = destination_path.as_ref().join("meta");
+    if !destination.is_dir() {
+        create_dir_all(&destination)?;
+    }
+    let destination = destination.join(format!("{}.json", edition));
+    let temp_destination = destination.with_extension("json.tmp");
+    write(&temp_destination, meta_string)?;
+    rename(&temp_destination, &destination)?;
+
+    Ok(())
+}
+
 fn try_convert_os_str_to_string(str: &OsStr) -> Result<String> {
     let string = str
         .to_str()
@@ -186,22 +506,315 @@ fn try_convert_os_str_to_string(str: &OsStr) -> Result<String> {
     Ok(string)
 }
 
-fn overlay_images<P: AsRef<Path>>(
-    size: u32,
-    image_paths: &[P],
-) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
-    let context = "Overlay image layers";
+/// Composites the frames of the layer images onto canvases of the given dimensions, along with
+/// the delay of every frame. Without animated layers this is a single frame.
+fn composite_frames(
+    dimensions: (u32, u32),
+    filter: FilterType,
+    animation: &AnimationConfiguration,
+    image_files: &[ImageFile],
+    cache: &LayerCache,
+) -> Result<Vec<(RgbaImage, u32)>> {
+    let layer_frames = image_files
+        .iter()
+        .map(|f| load_layer_frames(f, dimensions, filter, cache))
+        .collect::<Result<Vec<Arc<LayerFrames>>>>()?;
+    let delays = layer_frames
+        .iter()
+        .map(|f| f.get_delays().as_slice())
+        .collect::<Vec<&[u32]>>();
+
+    let frames = reconcile(&delays, animation)
+        .iter()
+        .map(|frame| {
+            let layer_images = layer_frames
+                .iter()
+                .zip(frame.get_indices())
+                .map(|(f, index)| &f.get_frames()[*index])
+                .collect::<Vec<&RgbaImage>>();
+
+            (
+                overlay_images(dimensions, image_files, &layer_images),
+                frame.get_delay(),
+            )
+        })
+        .collect();
+
+    Ok(frames)
+}
+
+/// Loads the frames of the layer image, transformed or, without a transform, resampled to the
+/// canvas dimensions.
+fn load_layer_frames(
+    image_file: &ImageFile,
+    (width, height): (u32, u32),
+    filter: FilterType,
+    cache: &LayerCache,
+) -> Result<Arc<LayerFrames>> {
+    let context = "Load layer frames";
+
+    let frames = match &image_file.transform {
+        Some(transform) if transform.is_resampled() => cache
+            .get_variant(&image_file.path, &format!("{:?}", transform), |img| {
+                transform_image(img, transform)
+            })
+            .context(context)?,
+        Some(_) => cache.get(&image_file.path).context(context)?,
+        None => {
+            let frames = cache.get(&image_file.path).context(context)?;
+            if frames.dimensions() == (width, height) {
+                frames
+            } else {
+                let variant = format!("resize {}x{} {:?}", width, height, filter);
+                cache
+                    .get_variant(&image_file.path, &variant, |img| {
+                        resize(img, width, height, filter)
+                    })
+                    .context(context)?
+            }
+        }
+    };
+
+    Ok(frames)
+}
 
+/// Composites the layer images onto a canvas of the given dimensions. Layer images without a
+/// transform are expected to be resampled to the canvas, transformed ones are placed in canvas
+/// pixels.
+fn overlay_images(
+    (width, height): (u32, u32),
+    image_files: &[ImageFile],
+    layer_images: &[&RgbaImage],
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
     // transparent black background image as base
     let bg_cb = |_, _| Rgba([0u8, 0u8, 0u8, 0u8]);
-    let mut base_img = ImageBuffer::from_fn(size, size, bg_cb);
+    let mut base_img = ImageBuffer::from_fn(width, height, bg_cb);
+
+    for (image_file, img) in image_files.iter().zip(layer_images) {
+        let (x, y) = match &image_file.transform {
+            Some(transform) => position(transform, base_img.dimensions(), img.dimensions()),
+            None => (0, 0),
+        };
+
+        if image_file.blend == BlendMode::Normal && image_file.opacity >= 1.0 && x >= 0 && y >= 0 {
+            overlay(&mut base_img, *img, x as u32, y as u32);
+        } else {
+            blend(
+                &mut base_img,
+                img,
+                (x, y),
+                image_file.blend,
+                image_file.opacity,
+            );
+        }
+    }
+
+    base_img
+}
+
+/// Blends the layer at the (possibly negative) position onto the base image using the separable
+/// blend modes of the W3C compositing specification, calculated on premultiplied RGBA and stored
+/// as straight alpha again.
+fn blend(
+    base_img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    (x, y): (i64, i64),
+    mode: BlendMode,
+    opacity: f32,
+) {
+    let start_x = x.max(0);
+    let start_y = y.max(0);
+    let end_x = (x + img.width() as i64).min(base_img.width() as i64);
+    let end_y = (y + img.height() as i64).min(base_img.height() as i64);
+
+    for base_y in start_y..end_y {
+        for base_x in start_x..end_x {
+            let source = *img.get_pixel((base_x - x) as u32, (base_y - y) as u32);
+            let backdrop = base_img.get_pixel_mut(base_x as u32, base_y as u32);
+            *backdrop = blend_pixel(*backdrop, source, mode, opacity);
+        }
+    }
+}
+
+fn blend_pixel(backdrop: Rgba<u8>, source: Rgba<u8>, mode: BlendMode, opacity: f32) -> Rgba<u8> {
+    let alpha_b = backdrop[3] as f32 / 255.0;
+    let alpha_s = source[3] as f32 / 255.0 * opacity;
+    let alpha_o = alpha_s + alpha_b * (1.0 - alpha_s);
+
+    if alpha_o <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
 
-    for image_path in image_paths {
-        let dyn_img = open(image_path).context(context)?;
-        let img = dyn_img.into_rgba8();
+    let mut pixel = [0u8; 4];
+    for c in 0..3 {
+        let cb = backdrop[c] as f32 / 255.0;
+        let cs = source[c] as f32 / 255.0;
 
-        overlay(&mut base_img, &img, 0, 0);
+        // premultiplied result: cs' * (1 - ab) + cb' * (1 - as) + as * ab * B(cb, cs)
+        let premultiplied = alpha_s * cs * (1.0 - alpha_b)
+            + alpha_b * cb * (1.0 - alpha_s)
+            + alpha_s * alpha_b * blend_channel(cb, cs, mode);
+
+        pixel[c] = (premultiplied / alpha_o * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    pixel[3] = (alpha_o * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    Rgba(pixel)
+}
+
+fn blend_channel(cb: f32, cs: f32, mode: BlendMode) -> f32 {
+    match mode {
+        BlendMode::Normal => cs,
+        BlendMode::Multiply => cb * cs,
+        BlendMode::Screen => cb + cs - cb * cs,
+        BlendMode::Overlay => {
+            if cb <= 0.5 {
+                2.0 * cb * cs
+            } else {
+                let cb = 2.0 * cb - 1.0;
+                cb + cs - cb * cs
+            }
+        }
+        BlendMode::SoftLight => {
+            if cs <= 0.5 {
+                cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+            } else {
+                let d = if cb <= 0.25 {
+                    ((16.0 * cb - 12.0) * cb + 4.0) * cb
+                } else {
+                    cb.sqrt()
+                };
+                cb + (2.0 * cs - 1.0) * (d - cb)
+            }
+        }
+        BlendMode::Add => (cb + cs).min(1.0),
     }
+}
 
-    Ok(base_img)
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use serde_json::json;
+
+    use super::*;
+    use crate::processor::quota::Quotas;
+    use crate::test_utils::{read_layers, test_edition_config, TempDir};
+
+    #[test]
+    fn blend_opaque_pixels() {
+        let backdrop = Rgba([200, 100, 0, 255]);
+        let source = Rgba([128, 255, 255, 255]);
+
+        assert_eq!(
+            blend_pixel(backdrop, source, BlendMode::Normal, 1.0),
+            source
+        );
+        assert_eq!(
+            blend_pixel(backdrop, source, BlendMode::Multiply, 1.0),
+            Rgba([100, 100, 0, 255])
+        );
+        assert_eq!(
+            blend_pixel(backdrop, source, BlendMode::Screen, 1.0),
+            Rgba([228, 255, 255, 255])
+        );
+        assert_eq!(
+            blend_pixel(backdrop, source, BlendMode::Add, 1.0),
+            Rgba([255, 255, 255, 255])
+        );
+    }
+
+    #[test]
+    fn blend_with_opacity_and_transparency() {
+        let backdrop = Rgba([0, 0, 0, 255]);
+        let source = Rgba([255, 255, 255, 255]);
+
+        assert_eq!(
+            blend_pixel(backdrop, source, BlendMode::Normal, 0.5),
+            Rgba([128, 128, 128, 255])
+        );
+        // blending onto a transparent backdrop keeps the source, whatever the mode is
+        assert_eq!(
+            blend_pixel(Rgba([0, 0, 0, 0]), source, BlendMode::Multiply, 1.0),
+            source
+        );
+        assert_eq!(
+            blend_pixel(backdrop, Rgba([255, 255, 255, 0]), BlendMode::Screen, 1.0),
+            backdrop
+        );
+    }
+
+    fn pick_images(layers: &[(&str, &[&str])], config: serde_json::Value) -> Vec<Option<Image>> {
+        let dir = TempDir::with_layers("image_rules", layers);
+        let layers = read_layers(&dir.join("layers"));
+        let edition_config = test_edition_config(json!({ "layers": [config] }));
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let quotas = Quotas::new(&edition_config)
+            .assign(&edition_config, &[0; 20], &mut rng)
+            .unwrap();
+
+        (0..20)
+            .map(|slot| {
+                Image::from_layers(&layers, &edition_config, 0, &quotas, slot, &mut rng).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn requires_same_file() {
+        let images = pick_images(
+            &[
+                ("Sleeve_Left", &["Red", "Blue#5"]),
+                ("Sleeve_Right", &["Red#5", "Blue"]),
+            ],
+            json!({"size": 1, "order": [
+                {"name": "Sleeve_Left"},
+                {"name": "Sleeve_Right", "requires": [{"layer": "Sleeve_Left", "sameFile": true}]}
+            ]}),
+        );
+
+        for image in images.iter().flatten() {
+            let traits = image.get_layer_traits();
+            assert_eq!(traits.len(), 2);
+            assert_eq!(traits[0].1, traits[1].1);
+        }
+        assert_eq!(images.iter().flatten().count(), images.len());
+    }
+
+    #[test]
+    fn only_if_draws_layer_with_trait() {
+        let images = pick_images(
+            &[("Hat", &["Cap", "Crown"]), ("Jewel", &["Gold"])],
+            json!({"size": 1, "order": [
+                {"name": "Hat"},
+                {"name": "Jewel", "onlyIf": [{"layer": "Hat", "file": "Crown"}]}
+            ]}),
+        );
+
+        let traits = images
+            .iter()
+            .map(|image| image.as_ref().unwrap().get_layer_traits())
+            .collect::<Vec<Vec<(&str, &str)>>>();
+        assert!(traits.iter().any(|t| t[0] == ("Hat", "Cap")));
+        assert!(traits.iter().any(|t| t[0] == ("Hat", "Crown")));
+        for traits in traits {
+            match traits[0] {
+                ("Hat", "Crown") => assert_eq!(traits[1..], [("Jewel", "Gold")]),
+                _ => assert_eq!(traits.len(), 1),
+            }
+        }
+    }
+
+    #[test]
+    fn requires_without_files_rejects_pick() {
+        let images = pick_images(
+            &[("Hat", &["Cap"]), ("Jewel", &["Gold", "Silver"])],
+            json!({"size": 1, "order": [
+                {"name": "Hat"},
+                {"name": "Jewel", "requires": [{"layer": "Hat", "file": "Cap", "files": ["Ruby"]}]}
+            ]}),
+        );
+
+        assert!(images.iter().all(|image| image.is_none()));
+    }
 }
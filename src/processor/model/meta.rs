@@ -1,46 +1,426 @@
+use anyhow::{Context, Result};
 use serde::Serialize;
+use serde_json::{json, Map, Number, Value};
 
-use crate::config::edition::EditionConfiguration;
+use crate::config::edition::{EditionConfiguration, LayerConfiguration, OneOfOneConfiguration};
+use crate::config::metadata::{
+    cip25_asset_name, AttributeConfiguration, CreatorConfiguration, DisplayType,
+    MetadataConfiguration, MetadataProfile,
+};
 use crate::processor::model::image::ImageFile;
 
-#[derive(Debug, Serialize)]
-pub(super) struct Meta {
+/// Maximum length (in bytes) of CIP-25 metadata strings, longer strings are split into arrays.
+const CIP25_MAX_STRING_LENGTH: usize = 64;
+
+#[derive(Debug)]
+pub(super) struct Meta<'a> {
+    edition: u32,
     name: String,
     description: String,
     image: String,
+    image_type: &'static str,
+    animation_url: Option<String>,
+    animation_type: Option<&'static str>,
     attributes: Vec<MetaAttribute>,
+    metadata_config: &'a MetadataConfiguration,
 }
 
-impl Meta {
+impl<'a> Meta<'a> {
     pub(super) fn new(
         edition: u32,
-        edition_config: &EditionConfiguration,
+        edition_config: &'a EditionConfiguration,
+        layer_config: &LayerConfiguration,
         image_files: &[ImageFile],
-    ) -> Meta {
-        let name = edition_config._get_name().to_string() + &format!(" #{}", edition);
-        let image = edition_config._get_ipfs_uri().to_string() + &format!("{}.png", edition);
-        let attributes = image_files.iter().map(MetaAttribute::from).collect();
+        extension: &str,
+        animation_extension: Option<&str>,
+    ) -> Meta<'a> {
+        let metadata_config = edition_config.get_metadata();
+        let name = format!(
+            "{} #{}",
+            layer_config
+                .get_name()
+                .unwrap_or_else(|| edition_config._get_name()),
+            edition
+        );
+        let image =
+            edition_config._get_ipfs_uri().to_string() + &format!("{}.{}", edition, extension);
+        let animation_url = animation_extension.map(|extension| {
+            edition_config.get_animation_uri().to_string() + &format!("{}.{}", edition, extension)
+        });
+        let mut attributes = image_files
+            .iter()
+            .map(|f| MetaAttribute::new(f, metadata_config))
+            .collect::<Vec<MetaAttribute>>();
+        // static attributes replace all generated attributes of the same trait type (e.g. of
+        // multi-pick layers), like the traits of the image
+        for attribute in layer_config.get_attributes() {
+            attributes.retain(|a| a.trait_type != attribute.get_trait_type());
+            attributes.push(MetaAttribute::from_attribute(attribute));
+        }
 
         Meta {
+            edition,
             name,
-            description: edition_config._get_description().to_string(),
+            description: layer_config
+                .get_description()
+                .unwrap_or_else(|| edition_config._get_description())
+                .to_string(),
             image,
+            image_type: media_type(extension),
+            animation_url,
+            animation_type: animation_extension.map(media_type),
             attributes,
+            metadata_config,
+        }
+    }
+
+    /// Metadata of a pre-rendered edition, its attributes are the configured ones.
+    pub(super) fn from_one_of_one(
+        edition: u32,
+        edition_config: &'a EditionConfiguration,
+        one_of_one: &OneOfOneConfiguration,
+        extension: &str,
+    ) -> Meta<'a> {
+        let name = match one_of_one.get_name() {
+            Some(name) => name.to_string(),
+            None => format!("{} #{}", edition_config._get_name(), edition),
+        };
+
+        Meta {
+            edition,
+            name,
+            description: one_of_one
+                .get_description()
+                .unwrap_or_else(|| edition_config._get_description())
+                .to_string(),
+            image: edition_config._get_ipfs_uri().to_string()
+                + &format!("{}.{}", edition, extension),
+            image_type: media_type(extension),
+            animation_url: None,
+            animation_type: None,
+            attributes: one_of_one
+                .get_attributes()
+                .iter()
+                .map(MetaAttribute::from_attribute)
+                .collect(),
+            metadata_config: edition_config.get_metadata(),
+        }
+    }
+
+    /// Metadata of the edition in the configured metadata standard.
+    pub(super) fn to_value(&self) -> Result<Value> {
+        match self.metadata_config.get_profile() {
+            MetadataProfile::Erc721 => self.to_erc721(),
+            MetadataProfile::Metaplex => self.to_metaplex(),
+            MetadataProfile::Cip25 => Ok(self.to_cip25()),
+        }
+    }
+}
+
+impl Meta<'_> {
+    fn to_erc721(&self) -> Result<Value> {
+        let config = self.metadata_config;
+
+        serde_json::to_value(Erc721Meta {
+            name: &self.name,
+            description: &self.description,
+            image: &self.image,
+            external_url: config.get_external_url(self.edition),
+            background_color: config.get_background_color(),
+            animation_url: self.animation_url.as_deref(),
+            attributes: &self.attributes,
+        })
+        .context("Serialize ERC-721 metadata")
+    }
+
+    fn to_metaplex(&self) -> Result<Value> {
+        let config = self.metadata_config;
+        let mut files = vec![MetaplexFile {
+            uri: &self.image,
+            file_type: self.image_type,
+        }];
+        if let (Some(uri), Some(file_type)) = (&self.animation_url, self.animation_type) {
+            files.push(MetaplexFile { uri, file_type });
         }
+
+        serde_json::to_value(MetaplexMeta {
+            name: &self.name,
+            symbol: config.get_symbol().unwrap_or_default(),
+            description: &self.description,
+            seller_fee_basis_points: config.get_seller_fee_basis_points().unwrap_or_default(),
+            image: &self.image,
+            animation_url: self.animation_url.as_deref(),
+            external_url: config.get_external_url(self.edition),
+            attributes: &self.attributes,
+            properties: MetaplexProperties {
+                files,
+                category: "image",
+                creators: config.get_creators(),
+            },
+        })
+        .context("Serialize Metaplex metadata")
     }
+
+    fn to_cip25(&self) -> Value {
+        let mut files = vec![json!({
+            "name": self.name,
+            "mediaType": self.image_type,
+            "src": cip25_string(&self.image),
+        })];
+        if let (Some(uri), Some(media_type)) = (&self.animation_url, self.animation_type) {
+            files.push(json!({
+                "name": self.name,
+                "mediaType": media_type,
+                "src": cip25_string(uri),
+            }));
+        }
+
+        // trait types of multi-pick layers map to the list of their values
+        let mut attributes = Map::new();
+        for attribute in &self.attributes {
+            match attributes.get_mut(&attribute.trait_type) {
+                Some(Value::Array(values)) => values.push(attribute.value.clone()),
+                Some(value) => *value = json!([value.clone(), attribute.value.clone()]),
+                None => {
+                    attributes.insert(attribute.trait_type.to_string(), attribute.value.clone());
+                }
+            }
+        }
+        let asset = json!({
+            "name": self.name,
+            "image": cip25_string(&self.image),
+            "mediaType": self.image_type,
+            "description": cip25_string(&self.description),
+            "files": files,
+            "attributes": attributes,
+        });
+
+        let mut assets = Map::new();
+        assets.insert(cip25_asset_name(&self.name), asset);
+        let mut policies = Map::new();
+        policies.insert(
+            self.metadata_config
+                .get_policy_id()
+                .unwrap_or_default()
+                .to_string(),
+            Value::Object(assets),
+        );
+        policies.insert("version".to_string(), json!("1.0"));
+
+        json!({ "721": policies })
+    }
+}
+
+#[derive(Serialize)]
+struct Erc721Meta<'a> {
+    name: &'a str,
+    description: &'a str,
+    image: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background_color: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    animation_url: Option<&'a str>,
+    attributes: &'a Vec<MetaAttribute>,
+}
+
+#[derive(Serialize)]
+struct MetaplexMeta<'a> {
+    name: &'a str,
+    symbol: &'a str,
+    description: &'a str,
+    seller_fee_basis_points: u16,
+    image: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    animation_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_url: Option<String>,
+    attributes: &'a Vec<MetaAttribute>,
+    properties: MetaplexProperties<'a>,
+}
+
+#[derive(Serialize)]
+struct MetaplexProperties<'a> {
+    files: Vec<MetaplexFile<'a>>,
+    category: &'a str,
+    creators: &'a Vec<CreatorConfiguration>,
+}
+
+#[derive(Serialize)]
+struct MetaplexFile<'a> {
+    uri: &'a str,
+    #[serde(rename = "type")]
+    file_type: &'a str,
 }
 
 #[derive(Debug, Serialize)]
 struct MetaAttribute {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_type: Option<DisplayType>,
     trait_type: String,
-    value: String,
+    value: Value,
 }
 
-impl From<&ImageFile> for MetaAttribute {
-    fn from(image_file: &ImageFile) -> Self {
+impl MetaAttribute {
+    fn from_attribute(attribute: &AttributeConfiguration) -> MetaAttribute {
         MetaAttribute {
-            trait_type: image_file.get_layer().to_string(),
-            value: image_file.get_name().to_string(),
+            display_type: attribute.get_display_type(),
+            trait_type: attribute.get_trait_type().to_string(),
+            value: attribute.get_value().clone(),
+        }
+    }
+
+    fn new(image_file: &ImageFile, metadata_config: &MetadataConfiguration) -> MetaAttribute {
+        let name = image_file.get_name();
+        // display types only apply to numeric values, other values are kept as they are
+        let number = metadata_config
+            .get_display_type(image_file.get_layer())
+            .and_then(|display_type| Some((display_type, name.parse::<Number>().ok()?)));
+
+        match number {
+            Some((display_type, number)) => MetaAttribute {
+                display_type: Some(display_type),
+                trait_type: image_file.get_layer().to_string(),
+                value: Value::Number(number),
+            },
+            None => MetaAttribute {
+                display_type: None,
+                trait_type: image_file.get_layer().to_string(),
+                value: json!(name),
+            },
+        }
+    }
+}
+
+/// Asset of an edition metadata file of any profile, CIP-25 assets are nested in the `721`
+/// policy map.
+pub(in super::super) fn find_asset(meta: &Value) -> Option<&Value> {
+    match meta.get("721") {
+        Some(policies) => policies
+            .as_object()?
+            .iter()
+            .find(|(policy, _)| *policy != "version")?
+            .1
+            .as_object()?
+            .values()
+            .next(),
+        None => Some(meta),
+    }
+}
+
+/// Reads a string, which might be split into an array of chunks (CIP-25).
+pub(in super::super) fn join_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(string) => Some(string.to_string()),
+        Value::Array(chunks) => chunks.iter().map(|c| c.as_str()).collect(),
+        _ => None,
+    }
+}
+
+fn media_type(extension: &str) -> &'static str {
+    match extension {
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Splits strings longer than the CIP-25 limit into an array of chunks.
+pub(in super::super) fn cip25_string(string: &str) -> Value {
+    if string.len() <= CIP25_MAX_STRING_LENGTH {
+        return json!(string);
+    }
+
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    for c in string.chars() {
+        if chunk.len() + c.len_utf8() > CIP25_MAX_STRING_LENGTH {
+            chunks.push(std::mem::take(&mut chunk));
         }
+        chunk.push(c);
+    }
+    chunks.push(chunk);
+
+    json!(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::test_utils::{create_test_images, test_config, test_edition_config, TempDir};
+
+    #[test]
+    fn cip25_strings_are_split() {
+        let uri = format!("ipfs://{}/1.png", "a".repeat(59));
+
+        assert_eq!(cip25_string("ipfs://short"), json!("ipfs://short"));
+        assert_eq!(
+            cip25_string(&uri),
+            json!([uri[..64].to_string(), uri[64..].to_string()])
+        );
+        assert_eq!(cip25_asset_name("Sdx Nft #12"), "SdxNft12");
+    }
+
+    #[test]
+    fn cip25_attributes_list_multi_pick_values() {
+        let edition_config = test_edition_config(json!({
+            "metadata": {"profile": "cip25", "policyId": "policy"}
+        }));
+        let one_of_one = serde_json::from_value(json!({"image": "one.png"})).unwrap();
+        let mut meta = Meta::from_one_of_one(1, &edition_config, &one_of_one, "png");
+        meta.attributes = [
+            ("Hat", "Cap"),
+            ("Badge", "Star"),
+            ("Badge", "Moon"),
+            ("Badge", "Sun"),
+        ]
+        .iter()
+        .map(|(trait_type, value)| MetaAttribute {
+            display_type: None,
+            trait_type: trait_type.to_string(),
+            value: json!(value),
+        })
+        .collect();
+
+        let asset = &meta.to_value().unwrap()["721"]["policy"]["Test1"];
+        assert_eq!(
+            asset["attributes"],
+            json!({"Hat": "Cap", "Badge": ["Star", "Moon", "Sun"]})
+        );
+        assert!(asset.get("dna").is_none());
+    }
+
+    #[test]
+    fn static_attributes_replace_all_picks_of_trait_type() {
+        let dir = TempDir::with_layers(
+            "static_attributes",
+            &[
+                ("Background", &["Blue"]),
+                ("Badge", &["Moon", "Star", "Sun"]),
+            ],
+        );
+        let config = test_config(json!({
+            "layers": [{
+                "size": 1,
+                "attributes": [{"trait_type": "Badge", "value": "Gold"}],
+                "order": [{"name": "Background"}, {"name": "Badge", "pickMin": 2, "pickMax": 2}]
+            }]
+        }));
+        create_test_images(&dir, &config, &[], 1).unwrap();
+
+        let meta: Value =
+            serde_json::from_str(&read_to_string(dir.join("out/meta/1.json")).unwrap()).unwrap();
+        assert_eq!(
+            meta["attributes"],
+            json!([
+                {"trait_type": "Background", "value": "Blue"},
+                {"trait_type": "Badge", "value": "Gold"}
+            ])
+        );
     }
 }
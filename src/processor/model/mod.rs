@@ -1,2 +1,5 @@
+mod animation;
 pub(super) mod image;
-mod meta;
+pub(super) mod meta;
+pub(super) mod one_of_one;
+mod transform;
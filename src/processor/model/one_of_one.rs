@@ -0,0 +1,109 @@
+use std::fs::read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use image::imageops::resize;
+
+use crate::config::app::AppConfiguration;
+use crate::config::edition::{EditionConfiguration, OneOfOneConfiguration};
+use crate::hashing::simple_sha256;
+use crate::layers_model::LayerFrames;
+use crate::logger::log_info;
+use crate::processor::encoder::encode;
+use crate::processor::model::image::{extra_images_dir_name, save_file, save_meta_file};
+use crate::processor::model::meta::Meta;
+
+/// Pre-rendered edition, which is saved in the output format instead of being composited.
+#[derive(Debug)]
+pub(in super::super) struct OneOfOne<'a> {
+    config: &'a OneOfOneConfiguration,
+    /// Hash of the image file, so it never collides with the DNA of generated editions.
+    dna: String,
+}
+
+impl<'a> OneOfOne<'a> {
+    pub(in super::super) fn try_from_config(
+        config: &'a OneOfOneConfiguration,
+    ) -> Result<OneOfOne<'a>> {
+        let image = read(config.get_image()).context(format!(
+            "Read one of one image ({})",
+            config.get_image().display()
+        ))?;
+
+        Ok(OneOfOne {
+            config,
+            dna: simple_sha256(image),
+        })
+    }
+
+    pub(in super::super) fn get_config(&self) -> &OneOfOneConfiguration {
+        self.config
+    }
+
+    pub(in super::super) fn get_dna(&self) -> &str {
+        &self.dna
+    }
+
+    /// Trait type and value pairs of the configured attributes.
+    pub(in super::super) fn get_traits(&self) -> Vec<(String, String)> {
+        self.config
+            .get_attributes()
+            .iter()
+            .map(|a| (a.get_trait_type().to_string(), a.get_value_string()))
+            .collect()
+    }
+
+    /// Saves the image, resampled to the image size (and every extra size), and its metadata.
+    pub(in super::super) fn save<L: AsRef<Path>, D: AsRef<Path>>(
+        &self,
+        edition: u32,
+        app_config: &AppConfiguration<L, D>,
+        edition_config: &EditionConfiguration,
+    ) -> Result<()> {
+        let destination_path = app_config.get_destination_dir();
+        let context = format!(
+            "Save one of one ({}) as edition ({}) at ({})",
+            self.config.get_image().display(),
+            edition,
+            destination_path.display()
+        );
+
+        // animated images are rejected by the validator, so the single frame is the image
+        let frames = LayerFrames::read(self.config.get_image()).context(context.clone())?;
+        let (width, height) = app_config.get_dimensions();
+        let image = resize(
+            &frames.get_frames()[0],
+            width,
+            height,
+            app_config.get_filter(),
+        );
+        let format = app_config.get_format();
+        save_file(
+            destination_path.join("images"),
+            edition,
+            format.get_extension(),
+            &encode(&image, format).context(context.clone())?,
+        )
+        .context(context.clone())?;
+
+        for (width, height) in app_config.get_extra_sizes() {
+            let extra_image = resize(&image, *width, *height, app_config.get_filter());
+            save_file(
+                destination_path.join(extra_images_dir_name((*width, *height))),
+                edition,
+                format.get_extension(),
+                &encode(&extra_image, format).context(context.clone())?,
+            )
+            .context(context.clone())?;
+        }
+
+        let meta =
+            Meta::from_one_of_one(edition, edition_config, self.config, format.get_extension());
+        let meta = meta.to_value().context(context.clone())?;
+        save_meta_file(destination_path, edition, &meta).context(context)?;
+
+        log_info(format!("Saved one of one #{}", edition));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use image::imageops::{
+    flip_horizontal, flip_vertical, resize, rotate180, rotate270, rotate90, FilterType,
+};
+use image::{Rgba, RgbaImage};
+
+use crate::config::edition::{Flip, TransformConfiguration};
+
+/// Scales, flips and rotates (in this order) the layer image.
+pub(super) fn transform_image(img: &RgbaImage, transform: &TransformConfiguration) -> RgbaImage {
+    let scale = transform.get_scale();
+    let mut transformed = if scale != 1.0 {
+        let width = ((img.width() as f32 * scale).round() as u32).max(1);
+        let height = ((img.height() as f32 * scale).round() as u32).max(1);
+
+        resize(img, width, height, FilterType::Lanczos3)
+    } else {
+        img.clone()
+    };
+
+    transformed = match transform.get_flip() {
+        Some(Flip::Horizontal) => flip_horizontal(&transformed),
+        Some(Flip::Vertical) => flip_vertical(&transformed),
+        Some(Flip::Both) => rotate180(&transformed),
+        None => transformed,
+    };
+
+    let rotation = transform.get_rotation();
+    if rotation == 90.0 {
+        rotate90(&transformed)
+    } else if rotation == 180.0 {
+        rotate180(&transformed)
+    } else if rotation == 270.0 {
+        rotate270(&transformed)
+    } else if rotation != 0.0 {
+        rotate(&transformed, rotation)
+    } else {
+        transformed
+    }
+}
+
+/// Top left position of the (transformed) layer image on the canvas.
+pub(super) fn position(
+    transform: &TransformConfiguration,
+    canvas: (u32, u32),
+    img: (u32, u32),
+) -> (i64, i64) {
+    let (factor_x, factor_y) = transform.get_anchor().get_factors();
+    let x = (canvas.0 as f32 - img.0 as f32) * factor_x;
+    let y = (canvas.1 as f32 - img.1 as f32) * factor_y;
+
+    (
+        x.round() as i64 + transform.get_x(),
+        y.round() as i64 + transform.get_y(),
+    )
+}
+
+/// Rotates clockwise by any angle around the center, the result is enlarged to the bounding box
+/// of the rotated image. Pixels are sampled bilinear on premultiplied alpha, to avoid dark edges.
+fn rotate(img: &RgbaImage, degrees: f32) -> RgbaImage {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let rotated_width = (width * cos.abs() + height * sin.abs()).ceil() as u32;
+    let rotated_height = (width * sin.abs() + height * cos.abs()).ceil() as u32;
+
+    let (center_x, center_y) = (width / 2.0, height / 2.0);
+    let (rotated_center_x, rotated_center_y) =
+        (rotated_width as f32 / 2.0, rotated_height as f32 / 2.0);
+
+    RgbaImage::from_fn(rotated_width, rotated_height, |x, y| {
+        // map the target pixel center back into the source image (inverse rotation)
+        let dx = x as f32 + 0.5 - rotated_center_x;
+        let dy = y as f32 + 0.5 - rotated_center_y;
+        let source_x = dx * cos + dy * sin + center_x - 0.5;
+        let source_y = -dx * sin + dy * cos + center_y - 0.5;
+
+        sample_bilinear(img, source_x, source_y)
+    })
+}
+
+fn sample_bilinear(img: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut premultiplied = [0f32; 4];
+
+    for (offset_x, offset_y, weight) in [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ] {
+        let (px, py) = (x0 as i64 + offset_x, y0 as i64 + offset_y);
+        if px < 0 || py < 0 || px >= img.width() as i64 || py >= img.height() as i64 {
+            continue;
+        }
+
+        let pixel = img.get_pixel(px as u32, py as u32);
+        let alpha = pixel[3] as f32 / 255.0;
+        for c in 0..3 {
+            premultiplied[c] += pixel[c] as f32 * alpha * weight;
+        }
+        premultiplied[3] += alpha * weight;
+    }
+
+    let alpha = premultiplied[3];
+    if alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    Rgba([
+        (premultiplied[0] / alpha).round().clamp(0.0, 255.0) as u8,
+        (premultiplied[1] / alpha).round().clamp(0.0, 255.0) as u8,
+        (premultiplied[2] / alpha).round().clamp(0.0, 255.0) as u8,
+        (alpha * 255.0).round().clamp(0.0, 255.0) as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(json: &str) -> TransformConfiguration {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn position_by_anchor_and_offset() {
+        let canvas = (100, 100);
+        let img = (20, 10);
+
+        assert_eq!(position(&transform("{}"), canvas, img), (0, 0));
+        assert_eq!(
+            position(&transform(r#"{"anchor": "center"}"#), canvas, img),
+            (40, 45)
+        );
+        assert_eq!(
+            position(
+                &transform(r#"{"anchor": "bottomRight", "x": -5, "y": 3}"#),
+                canvas,
+                img
+            ),
+            (75, 93)
+        );
+    }
+
+    #[test]
+    fn rotate_enlarges_to_bounding_box() {
+        let img = RgbaImage::from_pixel(10, 4, Rgba([255, 0, 0, 255]));
+
+        let rotated = transform_image(&img, &transform(r#"{"rotation": 90}"#));
+        assert_eq!(rotated.dimensions(), (4, 10));
+
+        let rotated = transform_image(&img, &transform(r#"{"rotation": 45}"#));
+        assert_eq!(rotated.dimensions(), (10, 10));
+        assert_eq!(rotated.get_pixel(5, 5), &Rgba([255, 0, 0, 255]));
+        assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+    }
+}
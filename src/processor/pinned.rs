@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::config::app::AppConfiguration;
+use crate::config::edition::EditionConfiguration;
+use crate::config::pinned::{read_pinned_editions, PinnedEdition};
+use crate::layers_model::Layers;
+use crate::logger::{log_error, log_warn};
+use crate::processor::manifest::ManifestEdition;
+use crate::processor::model::image::Image;
+use crate::processor::resume::read_dna_traits;
+
+/// Reads the pinned editions and resolves their traits (or the DNA of an edition in the
+/// destination directory) to images of the first layer configuration allowing them. Nothing is
+/// created, if any pinned edition is invalid.
+pub(super) fn read_pinned_images<L: AsRef<Path>, D: AsRef<Path>>(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    app_config: &AppConfiguration<L, D>,
+    path: &Path,
+) -> Result<Vec<(u32, Image)>> {
+    let context = format!("Pin editions of ({})", path.display());
+    let pinned_editions = read_pinned_editions(path).context(context.clone())?;
+
+    let dna_traits = if pinned_editions.iter().any(|p| p.get_dna().is_some()) {
+        read_dna_traits(app_config.get_destination_dir()).context(context.clone())?
+    } else {
+        HashMap::new()
+    };
+
+    let edition_size = edition_config.get_edition_size();
+    let one_of_ones = edition_config
+        .get_one_of_ones()
+        .iter()
+        .filter_map(|o| o.get_edition())
+        .collect::<HashSet<u32>>();
+    let mut issues = vec![];
+    let mut pinned = vec![];
+    for pinned_edition in &pinned_editions {
+        let edition = pinned_edition.get_edition();
+        let mut issue = |message: String| issues.push(format!("Edition #{}: {}", edition, message));
+
+        if edition == 0 || edition > edition_size {
+            issue(format!("out of the edition numbers 1 - {}", edition_size));
+        } else if one_of_ones.contains(&edition) {
+            issue("already taken by a one of one".to_string());
+        } else if pinned.iter().any(|(e, _)| *e == edition) {
+            issue("pinned twice".to_string());
+        }
+
+        match pinned_image(layers, edition_config, pinned_edition, &dna_traits) {
+            Ok(image) => {
+                if let Some((other, _)) = pinned
+                    .iter()
+                    .find(|(_, other): &&(u32, Image)| other.get_dna() == image.get_dna())
+                {
+                    issue(format!("same traits as the pinned edition #{}", other));
+                }
+                pinned.push((edition, image));
+            }
+            Err(err) => issue(format!("{:#}", err)),
+        }
+    }
+
+    for (index, layer_config) in edition_config.get_layers().iter().enumerate() {
+        let count = pinned
+            .iter()
+            .filter(|(_, image)| image.get_layer_config() == index)
+            .count() as u32;
+
+        if count > layer_config.get_size() {
+            issues.push(format!(
+                "Layer configuration #{} has {} pinned editions, but a size of {}",
+                index,
+                count,
+                layer_config.get_size()
+            ));
+        }
+    }
+
+    if !issues.is_empty() {
+        for issue in &issues {
+            log_error(issue);
+        }
+        bail!("{}: {} pinned editions are invalid", context, issues.len());
+    }
+    pinned.sort_by_key(|(edition, _)| *edition);
+
+    Ok(pinned)
+}
+
+/// Drops complete editions (of a resumed run), which are replaced by a pinned edition, or have
+/// the same DNA as a pinned edition at another number, so they get generated again.
+pub(super) fn drop_replaced_editions(
+    pinned: &[(u32, Image)],
+    complete_editions: &mut HashMap<u32, ManifestEdition>,
+) {
+    for (edition, image) in pinned {
+        if complete_editions
+            .get(edition)
+            .is_some_and(|complete| complete.get_dna() != image.get_dna())
+        {
+            log_warn(format!(
+                "Edition #{} is replaced by a pinned edition",
+                edition
+            ));
+            complete_editions.remove(edition);
+        }
+
+        let duplicates = complete_editions
+            .iter()
+            .filter(|(e, complete)| *e != edition && complete.get_dna() == image.get_dna())
+            .map(|(e, _)| *e)
+            .collect::<Vec<u32>>();
+        for duplicate in duplicates {
+            log_warn(format!(
+                "Edition #{} has the traits of the pinned edition #{} and will be regenerated",
+                duplicate, edition
+            ));
+            complete_editions.remove(&duplicate);
+        }
+    }
+}
+
+fn pinned_image(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    pinned_edition: &PinnedEdition,
+    dna_traits: &HashMap<String, Vec<(String, String)>>,
+) -> Result<Image> {
+    let traits = match pinned_edition.get_dna() {
+        Some(dna) => {
+            let traits = dna_traits
+                .get(dna)
+                .context(format!("no edition with the DNA ({}) found", dna))?;
+            let mut files: HashMap<&str, Vec<&str>> = HashMap::new();
+            for (layer, name) in traits {
+                files.entry(layer).or_default().push(name);
+            }
+
+            Some(files)
+        }
+        None => None,
+    };
+
+    let layer_configs = match pinned_edition.get_layer_configuration() {
+        Some(index) => vec![index],
+        None => (0..edition_config.get_layers().len()).collect(),
+    };
+
+    let mut errors = vec![];
+    for index in layer_configs {
+        let traits = traits
+            .clone()
+            .unwrap_or_else(|| pinned_edition.get_traits());
+
+        match Image::from_traits(layers, edition_config, index, &traits) {
+            Ok(image) => return Ok(image),
+            Err(err) => errors.push(format!("[layers #{}] {:#}", index, err)),
+        }
+    }
+
+    bail!("{}", errors.join("; "))
+}
@@ -0,0 +1,166 @@
+use std::fs::{read, write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::config::app::AppConfiguration;
+use crate::hashing::{concatenated_sha256, simple_sha256, starting_index};
+
+pub(super) const PROVENANCE_FILE_NAME: &str = "provenance.json";
+
+/// Commitment to the final images (and their order) which can be published before the reveal.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ProvenanceRecord {
+    /// SHA-256 of the concatenated image hashes, in edition order.
+    provenance_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    starting_index: Option<StartingIndex>,
+    editions: Vec<EditionProvenance>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartingIndex {
+    seed: String,
+    /// Token `t` shows the edition `(t - 1 + offset) % editions + 1`.
+    offset: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EditionProvenance {
+    edition: u32,
+    image: String,
+    sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_id: Option<u32>,
+}
+
+impl ProvenanceRecord {
+    /// Hashes the images of the editions `1..=edition_size` in the destination directory, stale
+    /// images of larger previous runs are ignored, just like editions left out by an unfilled
+    /// layer configuration.
+    pub(super) fn new<L: AsRef<Path>, D: AsRef<Path>>(
+        app_config: &AppConfiguration<L, D>,
+        edition_size: u32,
+    ) -> Result<ProvenanceRecord> {
+        let images_path = app_config.get_destination_dir().join("images");
+        let context = format!("Hash images in ({})", images_path.display());
+        let extension = app_config.get_format().get_extension();
+
+        let image_paths = (1..=edition_size)
+            .map(|edition| {
+                (
+                    edition,
+                    images_path.join(format!("{}.{}", edition, extension)),
+                )
+            })
+            .filter(|(_, path)| path.is_file())
+            .collect::<Vec<(u32, PathBuf)>>();
+
+        let hashes = image_paths
+            .par_iter()
+            .map(|(_, path)| {
+                let image = read(path).context(format!("Read image ({})", path.display()))?;
+
+                Ok(simple_sha256(image))
+            })
+            .collect::<Result<Vec<String>>>()
+            .context(context)?;
+
+        let starting_index = app_config
+            .get_starting_index_seed()
+            .map(|seed| StartingIndex {
+                seed: seed.to_string(),
+                offset: starting_index(seed, edition_size),
+            });
+        let editions = image_paths
+            .iter()
+            .zip(hashes.iter())
+            .map(|((edition, path), sha256)| EditionProvenance {
+                edition: *edition,
+                image: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                sha256: sha256.to_string(),
+                token_id: starting_index
+                    .as_ref()
+                    .map(|s| (*edition - 1 + edition_size - s.offset) % edition_size + 1),
+            })
+            .collect();
+
+        Ok(ProvenanceRecord {
+            provenance_hash: concatenated_sha256(&hashes),
+            starting_index,
+            editions,
+        })
+    }
+
+    pub(super) fn get_provenance_hash(&self) -> &str {
+        &self.provenance_hash
+    }
+
+    pub(super) fn save<P: AsRef<Path>>(&self, destination_path: P) -> Result<()> {
+        let context = format!(
+            "Save provenance record at ({})",
+            destination_path.as_ref().display()
+        );
+
+        let content = serde_json::to_string_pretty(self).context(context.clone())?;
+        write(
+            destination_path.as_ref().join(PROVENANCE_FILE_NAME),
+            content,
+        )
+        .context(context)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+
+    use super::*;
+    use crate::test_utils::{arg_matches, TempDir};
+
+    #[test]
+    fn token_ids_follow_edition_numbers() {
+        let dir = TempDir::new("provenance");
+        create_dir_all(dir.join("images")).unwrap();
+        // edition 4 is a stale image of a larger previous run
+        for edition in 1..=4 {
+            write(
+                dir.join("images").join(format!("{}.png", edition)),
+                edition.to_string(),
+            )
+            .unwrap();
+        }
+        let matches = arg_matches(&[
+            "-d",
+            dir.path().to_str().unwrap(),
+            "--starting-index-seed",
+            "0x01",
+        ]);
+
+        let record =
+            ProvenanceRecord::new(&AppConfiguration::from_arg_matches(&matches), 3).unwrap();
+        assert_eq!(record.starting_index.as_ref().unwrap().offset, 1);
+        assert_eq!(
+            record
+                .editions
+                .iter()
+                .map(|e| (e.edition, e.token_id.unwrap()))
+                .collect::<Vec<(u32, u32)>>(),
+            vec![(1, 3), (2, 1), (3, 2)]
+        );
+        assert_eq!(
+            record.provenance_hash,
+            concatenated_sha256(&["1", "2", "3"].map(simple_sha256))
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::config::edition::{EditionConfiguration, LayerConfiguration};
+
+/// Remaining trait quotas while picking the editions. The remaining quota files are assigned to
+/// the editions to pick up front, so every quota is met exactly once all editions are picked.
+#[derive(Debug)]
+pub(super) struct Quotas {
+    layers: BTreeMap<String, LayerQuota>,
+}
+
+#[derive(Debug)]
+struct LayerQuota {
+    /// Remaining editions per file, negative if exceeded (e.g. by pinned editions).
+    remaining: BTreeMap<String, i64>,
+    /// Remaining editions drawing a file without quota.
+    free: i64,
+}
+
+impl Quotas {
+    pub(super) fn new(edition_config: &EditionConfiguration) -> Quotas {
+        let layers = edition_config
+            .get_quotas()
+            .iter()
+            .map(|(layer, quotas)| {
+                let draws = edition_config
+                    .get_layers()
+                    .iter()
+                    .filter(|l| draws_layer(l, layer))
+                    .map(|l| l.get_size() as i64)
+                    .sum::<i64>();
+
+                let quota = LayerQuota {
+                    remaining: quotas
+                        .iter()
+                        .map(|(file, quota)| (file.to_string(), *quota as i64))
+                        .collect(),
+                    free: draws - quotas.values().map(|q| *q as i64).sum::<i64>(),
+                };
+
+                (layer.to_string(), quota)
+            })
+            .collect();
+
+        Quotas { layers }
+    }
+
+    /// Whether every quota layer of the layer configuration is part of the traits, it's missing
+    /// if its assigned file couldn't be picked.
+    pub(super) fn allows(
+        &self,
+        layer_config: &LayerConfiguration,
+        traits: &[(&str, &str)],
+    ) -> bool {
+        layer_config
+            .get_order()
+            .iter()
+            .filter(|lo| self.layers.contains_key(lo.get_name()))
+            .all(|lo| traits.iter().any(|(layer, _)| *layer == lo.get_name()))
+    }
+
+    /// Counts the traits of an edition against the quotas.
+    pub(super) fn take(&mut self, traits: &[(&str, &str)]) {
+        for (layer, name) in traits {
+            if let Some(quota) = self.layers.get_mut(*layer) {
+                match quota.remaining.get_mut(*name) {
+                    Some(remaining) => *remaining -= 1,
+                    None => quota.free -= 1,
+                }
+            }
+        }
+    }
+
+    /// Assigns the remaining quota files to the editions to pick, given by their layer
+    /// configuration. Every file is assigned as often as its remaining quota, the other editions
+    /// drawing the layer get a file without quota.
+    pub(super) fn assign<R: Rng>(
+        &self,
+        edition_config: &EditionConfiguration,
+        edition_layer_configs: &[usize],
+        rng: &mut R,
+    ) -> Result<QuotaAssignment> {
+        let mut slots = vec![BTreeMap::new(); edition_layer_configs.len()];
+
+        for (layer, quota) in &self.layers {
+            if let Some((name, remaining)) = quota.remaining.iter().find(|(_, r)| **r < 0) {
+                bail!(
+                    "Quota of ({}: {}) exceeded by {} by the complete and pinned editions",
+                    layer,
+                    name,
+                    -remaining
+                );
+            }
+            if quota.free < 0 {
+                bail!(
+                    "Files of ({}) without quota exceeded by {} by the complete and pinned editions",
+                    layer,
+                    -quota.free
+                );
+            }
+
+            let mut files = quota
+                .remaining
+                .iter()
+                .flat_map(|(name, remaining)| (0..*remaining).map(move |_| Some(name.to_string())))
+                .chain((0..quota.free).map(|_| None))
+                .collect::<Vec<Option<String>>>();
+            files.shuffle(rng);
+
+            let drawing = slots
+                .iter_mut()
+                .zip(edition_layer_configs)
+                .filter(|(_, index)| draws_layer(&edition_config.get_layers()[**index], layer))
+                .map(|(slot, _)| slot)
+                .collect::<Vec<&mut BTreeMap<String, Option<String>>>>();
+            if drawing.len() != files.len() {
+                bail!(
+                    "Quotas of ({}) are left for {} editions, but {} editions draw the layer",
+                    layer,
+                    files.len(),
+                    drawing.len()
+                );
+            }
+            for (slot, file) in drawing.into_iter().zip(files) {
+                slot.insert(layer.to_string(), file);
+            }
+        }
+
+        Ok(QuotaAssignment {
+            slots,
+            quota_files: self
+                .layers
+                .iter()
+                .map(|(layer, quota)| {
+                    (layer.to_string(), quota.remaining.keys().cloned().collect())
+                })
+                .collect(),
+        })
+    }
+
+    /// Descriptions of the quotas which aren't met exactly.
+    pub(super) fn get_unmet(&self) -> Vec<String> {
+        self.layers
+            .iter()
+            .flat_map(|(layer, quota)| {
+                quota
+                    .remaining
+                    .iter()
+                    .filter(|(_, remaining)| **remaining != 0)
+                    .map(move |(name, remaining)| {
+                        if *remaining > 0 {
+                            format!("Quota of ({}: {}) missed by {}", layer, name, remaining)
+                        } else {
+                            format!("Quota of ({}: {}) exceeded by {}", layer, name, -remaining)
+                        }
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Quota files assigned to the editions to pick, in picking order.
+#[derive(Debug)]
+pub(super) struct QuotaAssignment {
+    /// Assigned file per quota layer of every edition, none for any file without quota.
+    slots: Vec<BTreeMap<String, Option<String>>>,
+    /// Files with quota per quota layer.
+    quota_files: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl QuotaAssignment {
+    pub(super) fn has_layer(&self, layer: &str) -> bool {
+        self.quota_files.contains_key(layer)
+    }
+
+    /// Weight of the file of a quota layer for the pick of the edition, only its assigned file
+    /// (or the files without quota) can be picked.
+    pub(super) fn weight(&self, slot: usize, layer: &str, name: &str, rarity_weight: u32) -> f64 {
+        match self.slots[slot].get(layer) {
+            Some(Some(file)) if file == name => 1.0,
+            Some(Some(_)) => 0.0,
+            Some(None) if self.quota_files[layer].contains(name) => 0.0,
+            _ => rarity_weight as f64,
+        }
+    }
+
+    /// Swaps the quota files of a rejected edition with the ones of random editions picked after
+    /// it, so a file isn't stuck with an edition it can't be picked for (e.g. by the rules).
+    pub(super) fn reassign<R: Rng>(&mut self, slot: usize, rng: &mut R) {
+        let layers = self.slots[slot].keys().cloned().collect::<Vec<String>>();
+
+        for layer in layers {
+            let later = (slot..self.slots.len())
+                .filter(|s| self.slots[*s].contains_key(&layer))
+                .collect::<Vec<usize>>();
+            if let Some(other) = later.choose(rng) {
+                let file = self.slots[*other][&layer].clone();
+                let previous = self.slots[slot].insert(layer.to_string(), file);
+                self.slots[*other].insert(layer, previous.flatten());
+            }
+        }
+    }
+}
+
+fn draws_layer(layer_config: &LayerConfiguration, layer: &str) -> bool {
+    layer_config
+        .get_order()
+        .iter()
+        .any(|lo| lo.get_name() == layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::processor::manifest::read_manifest;
+    use crate::test_utils::{create_test_images, test_config, test_edition_config, TempDir};
+
+    #[test]
+    fn quota_files_are_assigned_to_drawing_editions() {
+        let edition_config = test_edition_config(json!({
+            "layers": [
+                {"size": 3, "order": [{"name": "Outline"}]},
+                {"size": 2, "order": [{"name": "Eyes"}]}
+            ],
+            "quotas": {"Outline": {"Gold": 1}}
+        }));
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let mut quotas = Quotas::new(&edition_config);
+        quotas.take(&[("Outline", "Silver"), ("Eyes", "Big")]);
+
+        let mut assignment = quotas
+            .assign(&edition_config, &[0, 0, 1, 1], &mut rng)
+            .unwrap();
+        assignment.reassign(0, &mut rng);
+        let gold = (0..2)
+            .find(|slot| assignment.weight(*slot, "Outline", "Gold", 1) == 1.0)
+            .unwrap();
+        let free = 1 - gold;
+        assert_eq!(assignment.weight(gold, "Outline", "Silver", 3), 0.0);
+        assert_eq!(assignment.weight(free, "Outline", "Gold", 1), 0.0);
+        assert_eq!(assignment.weight(free, "Outline", "Silver", 3), 3.0);
+        assert_eq!(assignment.weight(2, "Outline", "Gold", 1), 1.0);
+        assert_eq!(
+            quotas.get_unmet(),
+            vec!["Quota of (Outline: Gold) missed by 1"]
+        );
+
+        quotas.take(&[("Outline", "Gold"), ("Outline", "Gold")]);
+        assert_eq!(
+            quotas.get_unmet(),
+            vec!["Quota of (Outline: Gold) exceeded by 1"]
+        );
+        assert!(quotas
+            .assign(&edition_config, &[0, 1, 1], &mut rng)
+            .is_err());
+    }
+
+    fn quota_config(size: u32, quotas: Value) -> Value {
+        test_config(json!({
+            "layers": [{
+                "size": size,
+                "order": [{"name": "Background"}, {"name": "Hat"}, {"name": "Eyes"}]
+            }],
+            "exclusions": [{
+                "layer": "Background",
+                "file": "Red",
+                "excludes": [{"layer": "Hat", "file": "Crown"}]
+            }],
+            "quotas": quotas
+        }))
+    }
+
+    #[test]
+    fn quotas_are_met_exactly_with_exclusions() {
+        let dir = TempDir::with_layers(
+            "quotas",
+            &[
+                ("Background", &["Blue", "Green", "Red"]),
+                ("Hat", &["Beanie", "Cap", "Crown"]),
+                ("Eyes", &["Big", "Small"]),
+            ],
+        );
+        let config = quota_config(
+            8,
+            json!({"Background": {"Red": 3}, "Hat": {"Cap": 2, "Crown": 3}}),
+        );
+
+        for seed in 1..=5 {
+            create_test_images(&dir, &config, &[], seed).unwrap();
+
+            let mut counts = HashMap::new();
+            for edition in read_manifest(&dir.join("out")).unwrap().values() {
+                let traits = edition.get_layer_traits();
+                assert!(
+                    !(traits.contains(&("Background", "Red"))
+                        && traits.contains(&("Hat", "Crown")))
+                );
+                for (layer, name) in traits {
+                    *counts.entry(format!("{}: {}", layer, name)).or_insert(0) += 1;
+                }
+            }
+            assert_eq!(counts["Background: Red"], 3);
+            assert_eq!(counts["Hat: Crown"], 3);
+            assert_eq!(counts["Hat: Cap"], 2);
+            assert_eq!(counts["Hat: Beanie"], 3);
+        }
+    }
+
+    #[test]
+    fn infeasible_quotas_are_an_error() {
+        let dir = TempDir::with_layers(
+            "quotas_infeasible",
+            &[
+                ("Background", &["Blue", "Red"]),
+                ("Hat", &["Cap", "Crown"]),
+                ("Eyes", &["Big", "Small"]),
+            ],
+        );
+        // two editions need Red without Crown and two Crown without Red
+        let config = quota_config(3, json!({"Background": {"Red": 2}, "Hat": {"Crown": 2}}));
+
+        let err = create_test_images(&dir, &config, &["-m", "50"], 1).unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("Layer configuration #0 is left with 2 of 3 editions after 50 retries"));
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{create_dir_all, write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::layers_model::Layers;
+
+/// Occurrences of the traits of all editions, compared with the configured rarity weights,
+/// and rarity scores of every edition.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RarityReport {
+    editions: usize,
+    traits: Vec<TraitRarity>,
+    edition_scores: Vec<EditionRarity>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraitRarity {
+    trait_type: String,
+    value: String,
+    count: usize,
+    /// Share of editions containing the trait.
+    percentage: f64,
+    /// Share of the trait among all picks of its layer.
+    layer_percentage: f64,
+    weight: Option<u32>,
+    /// Share of the trait weight among all weights of its layer.
+    weight_percentage: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EditionRarity {
+    edition: u32,
+    trait_count: usize,
+    /// Product of the trait frequencies, lower is rarer.
+    statistical_rarity: f64,
+    /// Sum of the inverse trait (and trait count) frequencies, higher is rarer.
+    rarity_score: f64,
+    rank: usize,
+}
+
+impl RarityReport {
+    /// Creates the report from the (trait type, value) pairs of every edition.
+    pub(super) fn new(layers: &Layers, editions: &[(u32, Vec<(String, String)>)]) -> RarityReport {
+        let total = editions.len();
+        let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        let mut layer_counts: HashMap<&str, usize> = HashMap::new();
+        let mut trait_count_counts: HashMap<usize, usize> = HashMap::new();
+
+        for (_, traits) in editions {
+            for (trait_type, value) in traits {
+                *counts.entry((trait_type, value)).or_insert(0) += 1;
+                *layer_counts.entry(trait_type).or_insert(0) += 1;
+            }
+            *trait_count_counts.entry(traits.len()).or_insert(0) += 1;
+        }
+
+        let frequency = |count: usize| count as f64 / total.max(1) as f64;
+
+        let traits = counts
+            .iter()
+            .map(|((trait_type, value), count)| {
+                let weights = layers.get_file_weights(trait_type).unwrap_or_default();
+                let total_weight = weights.iter().map(|(_, w)| *w as u64).sum::<u64>();
+                let weight = weights
+                    .iter()
+                    .filter(|(name, _)| name == value)
+                    .map(|(_, w)| *w)
+                    .reduce(|a, b| a + b);
+
+                TraitRarity {
+                    trait_type: trait_type.to_string(),
+                    value: value.to_string(),
+                    count: *count,
+                    percentage: frequency(*count) * 100.0,
+                    layer_percentage: *count as f64 / layer_counts[trait_type] as f64 * 100.0,
+                    weight,
+                    weight_percentage: weight
+                        .filter(|_| total_weight > 0)
+                        .map(|w| w as f64 / total_weight as f64 * 100.0),
+                }
+            })
+            .collect();
+
+        let mut edition_scores = editions
+            .iter()
+            .map(|(edition, traits)| {
+                let frequencies = traits
+                    .iter()
+                    .map(|(trait_type, value)| {
+                        frequency(counts[&(trait_type.as_str(), value.as_str())])
+                    })
+                    .collect::<Vec<f64>>();
+                let trait_count_frequency = frequency(trait_count_counts[&traits.len()]);
+
+                EditionRarity {
+                    edition: *edition,
+                    trait_count: traits.len(),
+                    statistical_rarity: frequencies.iter().product(),
+                    rarity_score: frequencies.iter().map(|f| 1.0 / f).sum::<f64>()
+                        + 1.0 / trait_count_frequency,
+                    rank: 0,
+                }
+            })
+            .collect::<Vec<EditionRarity>>();
+
+        edition_scores.sort_by(|a, b| {
+            b.rarity_score
+                .total_cmp(&a.rarity_score)
+                .then(a.edition.cmp(&b.edition))
+        });
+        edition_scores
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, score)| score.rank = index + 1);
+        edition_scores.sort_by_key(|s| s.edition);
+
+        RarityReport {
+            editions: total,
+            traits,
+            edition_scores,
+        }
+    }
+
+    /// Saves the report as `rarity.json` and a human-readable `rarity.txt` table.
+    pub(super) fn save<P: AsRef<Path>>(&self, destination_path: P) -> Result<()> {
+        let context = format!(
+            "Save rarity report at ({})",
+            destination_path.as_ref().display()
+        );
+
+        if !destination_path.as_ref().is_dir() {
+            create_dir_all(&destination_path).context(context.clone())?;
+        }
+
+        let report_json = serde_json::to_string_pretty(self).context(context.clone())?;
+        write(destination_path.as_ref().join("rarity.json"), report_json)
+            .context(context.clone())?;
+        write(
+            destination_path.as_ref().join("rarity.txt"),
+            self.to_table(),
+        )
+        .context(context)?;
+
+        Ok(())
+    }
+
+    fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:<24} {:<24} {:>8} {:>10} {:>10} {:>10} {:>10}\n",
+            "Trait type", "Value", "Count", "Editions", "In layer", "Weight", "Expected"
+        );
+        table += &"-".repeat(102);
+        table += "\n";
+
+        for t in &self.traits {
+            table += &format!(
+                "{:<24} {:<24} {:>8} {:>9.2}% {:>9.2}% {:>10} {:>10}\n",
+                t.trait_type,
+                t.value,
+                t.count,
+                t.percentage,
+                t.layer_percentage,
+                t.weight.map_or("-".to_string(), |w| w.to_string()),
+                t.weight_percentage
+                    .map_or("-".to_string(), |p| format!("{:.2}%", p)),
+            );
+        }
+
+        table += &format!(
+            "\n{:>8} {:>8} {:>12} {:>14}\n",
+            "Edition", "Rank", "Traits", "Score"
+        );
+        table += &"-".repeat(45);
+        table += "\n";
+
+        let mut ranked = self.edition_scores.iter().collect::<Vec<&EditionRarity>>();
+        ranked.sort_by_key(|s| s.rank);
+        for s in ranked {
+            table += &format!(
+                "{:>8} {:>8} {:>12} {:>14.2}\n",
+                s.edition, s.rank, s.trait_count, s.rarity_score
+            );
+        }
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traits(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|(t, v)| (t.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn rarest_edition_ranks_first() {
+        let layers = Layers::default();
+        let editions = vec![
+            (1, traits(&[("Eyes", "Red"), ("Hat", "Cap")])),
+            (2, traits(&[("Eyes", "Red"), ("Hat", "Cap")])),
+            (3, traits(&[("Eyes", "Blue"), ("Hat", "Cap")])),
+        ];
+
+        let report = RarityReport::new(&layers, &editions);
+
+        assert_eq!(report.editions, 3);
+        assert_eq!(report.traits.len(), 3);
+        assert_eq!(report.edition_scores[2].rank, 1);
+        assert!((report.edition_scores[2].statistical_rarity - 1.0 / 3.0).abs() < 1e-9);
+        assert!((report.traits[0].percentage - 100.0 / 3.0).abs() < 1e-9);
+    }
+}
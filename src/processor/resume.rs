@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde_json::Value;
+
+use crate::config::app::AppConfiguration;
+use crate::layers_model::LayerFrames;
+use crate::logger::log_warn;
+use crate::processor::encoder::is_readable;
+use crate::processor::manifest::{read_manifest, ManifestEdition};
+use crate::processor::model::image::{extra_images_dir_name, ANIMATIONS_DIR_NAME};
+
+/// Reads the editions of a previous run from the run manifest of the destination directory,
+/// mapping the edition number to its record. Only editions with a readable metadata file and
+/// images (of every extra size, and the animation if any) are complete, half-written editions
+/// are skipped, so they get regenerated.
+pub(super) fn read_complete_editions<L: AsRef<Path>, D: AsRef<Path>>(
+    app_config: &AppConfiguration<L, D>,
+) -> Result<HashMap<u32, ManifestEdition>> {
+    let destination_path = app_config.get_destination_dir();
+    let context = format!(
+        "Read complete editions from ({})",
+        destination_path.display()
+    );
+    let meta_dir = destination_path.join("meta");
+    let images_dirs = std::iter::once("images".to_string())
+        .chain(
+            app_config
+                .get_extra_sizes()
+                .iter()
+                .map(|s| extra_images_dir_name(*s)),
+        )
+        .map(|dir| destination_path.join(dir))
+        .collect::<Vec<PathBuf>>();
+
+    let manifest = read_manifest(destination_path).context(context)?;
+    if manifest.is_empty() && meta_dir.is_dir() {
+        log_warn("No run manifest found, all editions will be regenerated");
+    }
+
+    let format = app_config.get_format();
+    let animations_dir = destination_path.join(ANIMATIONS_DIR_NAME);
+    let animation_extension = app_config.get_animation().get_format().get_extension();
+
+    // decoding every image is the only reliable way to detect truncated files
+    let editions = manifest
+        .into_par_iter()
+        .filter_map(|(edition, manifest_edition)| {
+            let is_meta_complete = read_to_string(meta_dir.join(format!("{}.json", edition)))
+                .ok()
+                .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+                .is_some();
+            let is_image_complete = images_dirs.iter().all(|dir| {
+                let image_path = dir.join(format!("{}.{}", edition, format.get_extension()));
+
+                is_readable(image_path, format)
+            });
+            let is_animation_complete = !manifest_edition.is_animated()
+                || LayerFrames::read(
+                    animations_dir.join(format!("{}.{}", edition, animation_extension)),
+                )
+                .is_ok();
+
+            if is_meta_complete && is_image_complete && is_animation_complete {
+                Some((edition, manifest_edition))
+            } else {
+                log_warn(format!(
+                    "Edition #{} is incomplete and will be regenerated",
+                    edition
+                ));
+
+                None
+            }
+        })
+        .collect();
+
+    Ok(editions)
+}
+
+/// Layer traits of the editions in the destination directory by their DNA.
+pub(super) fn read_dna_traits(
+    destination_path: &Path,
+) -> Result<HashMap<String, Vec<(String, String)>>> {
+    let traits = read_manifest(destination_path)?
+        .into_values()
+        .map(|edition| {
+            let traits = edition
+                .get_layer_traits()
+                .iter()
+                .map(|(layer, name)| (layer.to_string(), name.to_string()))
+                .collect();
+
+            (edition.get_dna().to_string(), traits)
+        })
+        .collect();
+
+    Ok(traits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::fs::{read, remove_file, write, OpenOptions};
+    use std::io::Write;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::processor::manifest::MANIFEST_FILE_NAME;
+    use crate::test_utils::{arg_matches, create_test_images, test_config, TempDir};
+
+    #[test]
+    fn resume_regenerates_incomplete_editions() {
+        let dir = TempDir::with_layers(
+            "resume",
+            &[
+                ("Background", &["Blue", "Green", "Red"]),
+                ("Hat", &["Cap", "Crown", "None"]),
+            ],
+        );
+        let config = test_config(json!({
+            "layers": [{"size": 6, "order": [{"name": "Background"}, {"name": "Hat"}]}]
+        }));
+        create_test_images(&dir, &config, &[], 1).unwrap();
+        let out = dir.join("out");
+        let manifest = read_manifest(&out).unwrap();
+        assert_eq!(manifest.len(), 6);
+
+        // truncated image, missing and corrupt metadata and a partially written manifest line
+        let image = read(out.join("images").join("2.png")).unwrap();
+        write(out.join("images").join("2.png"), &image[..image.len() / 2]).unwrap();
+        remove_file(out.join("meta").join("3.json")).unwrap();
+        write(out.join("meta").join("4.json"), r#"{"name": "#).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(out.join(MANIFEST_FILE_NAME))
+            .unwrap()
+            .write_all(br#"{"edition":5,"dna":"#)
+            .unwrap();
+
+        let matches = arg_matches(&["-d", out.to_str().unwrap(), "-s", "4"]);
+        let app_config = AppConfiguration::from_arg_matches(&matches);
+        let complete = read_complete_editions(&app_config).unwrap();
+        let mut numbers = complete.keys().copied().collect::<Vec<u32>>();
+        numbers.sort();
+        assert_eq!(numbers, vec![1, 5, 6]);
+
+        create_test_images(&dir, &config, &["--resume"], 2).unwrap();
+        let resumed = read_manifest(&out).unwrap();
+        assert_eq!(resumed.len(), 6);
+        for edition in numbers {
+            assert_eq!(resumed[&edition].get_dna(), manifest[&edition].get_dna());
+        }
+        let dna = resumed
+            .values()
+            .map(|e| e.get_dna())
+            .collect::<HashSet<&str>>();
+        assert_eq!(dna.len(), 6);
+        for edition in 1..=6 {
+            let image_path = out.join("images").join(format!("{}.png", edition));
+            assert!(is_readable(image_path, app_config.get_format()));
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, read_dir, write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+use crate::config::app::AppConfiguration;
+use crate::config::edition::EditionConfiguration;
+use crate::config::uri::UriConfiguration;
+use crate::logger::{log_error, log_warn};
+use crate::processor::collection::{edition_number, read_edition_metas, save_collection_metadata};
+use crate::processor::model::image::ANIMATIONS_DIR_NAME;
+use crate::processor::model::meta::{cip25_string, find_asset, join_string};
+
+/// File of an edition linked by its metadata.
+struct LinkedFile {
+    uri: String,
+    extension: String,
+}
+
+/// Rewrites the image (and animation) URIs of the metadata files in the destination directory,
+/// in place or into the output directory, and saves the collection metadata again. Nothing is
+/// written, if any edition doesn't match its image file. Returns the number of updated editions.
+pub fn update_uris<L: AsRef<Path>, D: AsRef<Path>>(
+    edition_config: &EditionConfiguration,
+    app_config: &AppConfiguration<L, D>,
+    uri_config: &UriConfiguration,
+) -> Result<usize> {
+    let destination_path = app_config.get_destination_dir();
+    let output_path = uri_config
+        .get_output_dir()
+        .map_or(destination_path, Path::new);
+    let context = format!(
+        "Update URIs of editions in ({})",
+        destination_path.display()
+    );
+
+    let metas = read_edition_metas(destination_path, edition_config.get_edition_size())
+        .context(context.clone())?;
+
+    let mut issues = vec![];
+    let mut linked_files = vec![];
+    for (edition, meta) in &metas {
+        match linked_edition_files(destination_path, *edition, meta) {
+            Ok(files) => linked_files.push(files),
+            Err(err) => issues.push(format!("Edition #{}: {:#}", edition, err)),
+        }
+    }
+    issues.append(&mut orphan_images(
+        destination_path,
+        &metas,
+        edition_config.get_edition_size(),
+    ));
+
+    if !issues.is_empty() {
+        for issue in &issues {
+            log_error(issue);
+        }
+        bail!(
+            "{}: {} editions don't match their files, no metadata was updated",
+            context,
+            issues.len()
+        );
+    }
+
+    let has_animations = linked_files
+        .iter()
+        .any(|(_, animation)| animation.is_some());
+    if has_animations && !uri_config.has_animation_uri() {
+        log_warn("Editions have animations, but no 'animation-uri' is provided to update them");
+    }
+
+    let updated = linked_files.len();
+    let meta_dir = output_path.join("meta");
+    create_dir_all(&meta_dir).context(context.clone())?;
+
+    for ((edition, mut meta), (image, animation)) in metas.into_iter().zip(linked_files) {
+        let is_cip25 = meta.get("721").is_some();
+        let uri_value = |uri: String| {
+            if is_cip25 {
+                cip25_string(&uri)
+            } else {
+                json!(uri)
+            }
+        };
+
+        let image_uri = uri_config
+            .get_image_uri(edition, &image.extension)
+            .context(context.clone())?;
+        replace_uri(&mut meta, &image.uri, &uri_value(image_uri));
+
+        if let Some(animation) = animation {
+            if let Some(animation_uri) = uri_config.get_animation_uri(edition, &animation.extension)
+            {
+                let animation_uri = animation_uri.context(context.clone())?;
+                replace_uri(&mut meta, &animation.uri, &uri_value(animation_uri));
+            }
+        }
+
+        let meta_string = serde_json::to_string_pretty(&meta).context(context.clone())?;
+        write(meta_dir.join(format!("{}.json", edition)), meta_string).context(context.clone())?;
+    }
+
+    save_collection_metadata(edition_config, output_path).context(context)?;
+
+    Ok(updated)
+}
+
+/// Reads the image (and animation) linked by the metadata and checks that they are the files of
+/// the same edition in the destination directory.
+fn linked_edition_files(
+    destination_path: &Path,
+    edition: u32,
+    meta: &Value,
+) -> Result<(LinkedFile, Option<LinkedFile>)> {
+    let asset = find_asset(meta).context("Metadata has no asset")?;
+
+    let image_uri = asset
+        .get("image")
+        .and_then(join_string)
+        .context("Metadata has no image")?;
+    let image = linked_file(destination_path.join("images"), edition, image_uri)?;
+
+    // CIP-25 lists the animation as additional file
+    let animation_uri = match asset.get("animation_url") {
+        Some(animation_url) => join_string(animation_url),
+        None => asset
+            .get("files")
+            .and_then(|files| files.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|file| file.get("src").and_then(join_string))
+            .find(|src| *src != image.uri),
+    };
+    let animation = match animation_uri {
+        Some(uri) => Some(linked_file(
+            destination_path.join(ANIMATIONS_DIR_NAME),
+            edition,
+            uri,
+        )?),
+        None => None,
+    };
+
+    Ok((image, animation))
+}
+
+fn linked_file<P: AsRef<Path>>(dir: P, edition: u32, uri: String) -> Result<LinkedFile> {
+    let file_name = uri.rsplit('/').next().unwrap_or_default();
+    let (stem, extension) = file_name
+        .rsplit_once('.')
+        .context(format!("URI ({}) has no file extension", uri))?;
+
+    if stem != edition.to_string() {
+        bail!("URI ({}) links the file of another edition", uri);
+    }
+    let path = dir.as_ref().join(file_name);
+    if !path.is_file() {
+        bail!("Linked file ({}) doesn't exist", path.display());
+    }
+
+    Ok(LinkedFile {
+        extension: extension.to_string(),
+        uri,
+    })
+}
+
+/// Images of the editions `1..=edition_size` without a metadata file, e.g. of a half-written
+/// edition. Stale images of larger previous runs are ignored.
+fn orphan_images(
+    destination_path: &Path,
+    metas: &[(u32, Value)],
+    edition_size: u32,
+) -> Vec<String> {
+    let editions = metas
+        .iter()
+        .map(|(edition, _)| *edition)
+        .collect::<HashSet<u32>>();
+
+    let mut orphans = read_dir(destination_path.join("images"))
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            edition_number(p)
+                .is_some_and(|edition| edition <= edition_size && !editions.contains(&edition))
+        })
+        .map(|p| format!("Image ({}) has no metadata", p.display()))
+        .collect::<Vec<String>>();
+    orphans.sort();
+
+    orphans
+}
+
+/// Replaces every occurrence of the URI, CIP-25 URIs might be split into chunks.
+fn replace_uri(value: &mut Value, uri: &str, new_uri: &Value) {
+    if join_string(value).as_deref() == Some(uri) {
+        *value = new_uri.clone();
+        return;
+    }
+
+    match value {
+        Value::Array(values) => values.iter_mut().for_each(|v| replace_uri(v, uri, new_uri)),
+        Value::Object(values) => values
+            .values_mut()
+            .for_each(|v| replace_uri(v, uri, new_uri)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_plain_and_split_uris() {
+        let long_uri = format!("ipfs://{}/1.png", "a".repeat(60));
+        let mut meta = json!({
+            "image": "ipfs://old/1.png",
+            "properties": { "files": [{ "uri": "ipfs://old/1.png" }] },
+            "files": [{ "src": cip25_string(&long_uri) }],
+            "name": "Nft #1"
+        });
+
+        replace_uri(&mut meta, "ipfs://old/1.png", &json!("ipfs://new/1.png"));
+        replace_uri(&mut meta, &long_uri, &json!("ipfs://short/1.png"));
+
+        assert_eq!(
+            meta,
+            json!({
+                "image": "ipfs://new/1.png",
+                "properties": { "files": [{ "uri": "ipfs://new/1.png" }] },
+                "files": [{ "src": "ipfs://short/1.png" }],
+                "name": "Nft #1"
+            })
+        );
+    }
+}
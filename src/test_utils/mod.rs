@@ -0,0 +1,136 @@
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+use clap::ArgMatches;
+use image::{ImageBuffer, Rgba};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde_json::{json, Value};
+
+use crate::app::build_app;
+use crate::config::app::AppConfiguration;
+use crate::config::edition::EditionConfiguration;
+use crate::layers_model::Layers;
+use crate::processor::create_images;
+
+static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Unique directory below the system temp dir, removed again on drop.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!(
+            "sdx_nft_art_engine_{}_{}_{}",
+            name,
+            std::process::id(),
+            TEMP_DIR_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        create_dir_all(&path).unwrap();
+
+        TempDir { path }
+    }
+
+    /// Unique directory with the layer files written to `layers`, see `write_layers`.
+    pub fn with_layers(name: &str, layers: &[(&str, &[&str])]) -> TempDir {
+        let dir = TempDir::new(name);
+        write_layers(&dir.join("layers"), layers);
+
+        dir
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.path);
+    }
+}
+
+/// Writes a 4x4 image for every file of the layers, e.g. `("Hat", &["Cap#5", "Crown"])`.
+pub fn write_layers(dir: &Path, layers: &[(&str, &[&str])]) {
+    for (index, (layer, files)) in layers.iter().enumerate() {
+        create_dir_all(dir.join(layer)).unwrap();
+        for (file_index, file) in files.iter().enumerate() {
+            let pixel = Rgba([index as u8 * 40, file_index as u8 * 40, 100, 255]);
+            ImageBuffer::from_pixel(4, 4, pixel)
+                .save(dir.join(layer).join(format!("{}.png", file)))
+                .unwrap();
+        }
+    }
+}
+
+/// Edition configuration without layers, the top-level fields of `overrides` replace the
+/// defaults.
+pub fn test_config(overrides: Value) -> Value {
+    let mut config = json!({
+        "name": "Test",
+        "description": "Test",
+        "ipfsUri": "ipfs://Test/",
+        "layers": []
+    });
+    for (key, value) in overrides.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+
+    config
+}
+
+pub fn test_edition_config(overrides: Value) -> EditionConfiguration {
+    serde_json::from_value(test_config(overrides)).unwrap()
+}
+
+pub fn write_json(path: &Path, value: &Value) {
+    write(path, serde_json::to_string_pretty(value).unwrap()).unwrap();
+}
+
+/// Parses command line arguments the same way the binary does.
+pub fn arg_matches(args: &[&str]) -> ArgMatches {
+    build_app().get_matches_from(std::iter::once("sdx_nft_art_engine").chain(args.iter().copied()))
+}
+
+pub fn read_layers(dir: &Path) -> Layers {
+    let matches = arg_matches(&["-l", dir.to_str().unwrap()]);
+
+    Layers::from_config(&AppConfiguration::from_arg_matches(&matches))
+}
+
+/// Creates the images of the edition configuration from the layers in `dir/layers` into `dir/out`.
+pub fn create_test_images(dir: &TempDir, config: &Value, args: &[&str], seed: u64) -> Result<()> {
+    let config_path = dir.join("config.json");
+    write_json(&config_path, config);
+    let edition_config = EditionConfiguration::try_from_path(&config_path)?;
+
+    let layers_dir = dir.join("layers");
+    let destination = dir.join("out");
+    let mut run_args = vec![
+        "-l",
+        layers_dir.to_str().unwrap(),
+        "-d",
+        destination.to_str().unwrap(),
+        "-s",
+        "4",
+    ];
+    run_args.extend(args);
+    let matches = arg_matches(&run_args);
+    let app_config = AppConfiguration::from_arg_matches(&matches);
+    let layers = Layers::from_config(&app_config);
+
+    create_images(
+        &layers,
+        &edition_config,
+        &app_config,
+        &mut ChaCha20Rng::seed_from_u64(seed),
+    )
+}
@@ -0,0 +1,798 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::capacity::{capacity, combinations_with_file};
+use crate::config::edition::{EditionConfiguration, LayerConfiguration};
+use crate::config::metadata::{cip25_asset_name, MetadataProfile, CIP25_MAX_ASSET_NAME_LENGTH};
+use crate::layers_model::{LayerFrames, Layers};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn get_errors(&self) -> usize {
+        self.errors
+    }
+
+    pub fn get_warnings(&self) -> usize {
+        self.warnings
+    }
+
+    pub fn get_issues(&self) -> &Vec<ValidationIssue> {
+        &self.issues
+    }
+}
+
+impl From<Vec<ValidationIssue>> for ValidationReport {
+    fn from(issues: Vec<ValidationIssue>) -> Self {
+        let errors = issues
+            .iter()
+            .filter(|i| i.severity == Severity::Error)
+            .count();
+
+        ValidationReport {
+            valid: errors == 0,
+            errors,
+            warnings: issues.len() - errors,
+            issues,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssue {
+    severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layer_configuration: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    message: String,
+}
+
+impl ValidationIssue {
+    pub fn error<S: AsRef<str>>(message: S) -> ValidationIssue {
+        ValidationIssue {
+            severity: Severity::Error,
+            layer_configuration: None,
+            layer: None,
+            file: None,
+            message: message.as_ref().to_string(),
+        }
+    }
+
+    pub fn warning<S: AsRef<str>>(message: S) -> ValidationIssue {
+        ValidationIssue {
+            severity: Severity::Warning,
+            ..ValidationIssue::error(message)
+        }
+    }
+
+    pub fn get_severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn with_layer_configuration(mut self, index: usize) -> ValidationIssue {
+        self.layer_configuration = Some(index);
+        self
+    }
+
+    fn with_layer<S: AsRef<str>>(mut self, layer: S) -> ValidationIssue {
+        self.layer = Some(layer.as_ref().to_string());
+        self
+    }
+
+    fn with_file<P: AsRef<Path>>(mut self, file: P) -> ValidationIssue {
+        self.file = Some(file.as_ref().display().to_string());
+        self
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(index) = self.layer_configuration {
+            write!(f, "[layers #{}] ", index)?;
+        }
+        if let Some(layer) = &self.layer {
+            write!(f, "[{}] ", layer)?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, "[{}] ", file)?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Validates the edition configuration against the layers directory, without creating any image.
+pub fn validate(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    dimensions: (u32, u32),
+) -> ValidationReport {
+    let mut issues = vec![];
+
+    for (index, layer_config) in edition_config.get_layers().iter().enumerate() {
+        issues.append(
+            &mut check_layer_order(layers, layer_config)
+                .into_iter()
+                .map(|i| i.with_layer_configuration(index))
+                .collect(),
+        );
+        issues.append(
+            &mut check_layer_files(layers, layer_config, dimensions)
+                .into_iter()
+                .map(|i| i.with_layer_configuration(index))
+                .collect(),
+        );
+        issues.append(
+            &mut check_edition_size(layers, edition_config, layer_config)
+                .into_iter()
+                .map(|i| i.with_layer_configuration(index))
+                .collect(),
+        );
+    }
+    issues.append(&mut check_layer_rules(layers, edition_config));
+    issues.append(&mut check_metadata(edition_config));
+    issues.append(&mut check_one_of_ones(edition_config));
+    issues.append(&mut check_quotas(layers, edition_config));
+
+    ValidationReport::from(issues)
+}
+
+/// Checks that the profile-specific metadata fields and the collection royalties are complete
+/// and consistent.
+pub fn check_metadata(edition_config: &EditionConfiguration) -> Vec<ValidationIssue> {
+    let metadata = edition_config.get_metadata();
+    let mut issues = vec![];
+
+    if metadata.get_seller_fee_basis_points().unwrap_or_default() > 10000 {
+        issues.push(ValidationIssue::error(
+            "Metadata sellerFeeBasisPoints exceeds 10000 (100%)",
+        ));
+    }
+    let collection = edition_config.get_collection();
+    if collection.get_seller_fee_basis_points().unwrap_or_default() > 10000 {
+        issues.push(ValidationIssue::error(
+            "Collection sellerFeeBasisPoints exceeds 10000 (100%)",
+        ));
+    }
+
+    match metadata.get_profile() {
+        MetadataProfile::Metaplex => {
+            let shares = metadata
+                .get_creators()
+                .iter()
+                .map(|c| c.get_share() as u32)
+                .sum::<u32>();
+
+            if !metadata.get_creators().is_empty() && shares != 100 {
+                issues.push(ValidationIssue::error(format!(
+                    "Metadata creator shares sum up to {}, expected 100",
+                    shares
+                )));
+            }
+        }
+        MetadataProfile::Cip25 => {
+            if metadata.get_policy_id().is_none() {
+                issues.push(ValidationIssue::error(
+                    "Metadata profile cip25 requires a policyId",
+                ));
+            }
+
+            // the highest edition number gives the longest name
+            let edition_size = edition_config.get_edition_size();
+            let default_name = format!("{} #{}", edition_config._get_name(), edition_size);
+            let names = edition_config
+                .get_layers()
+                .iter()
+                .map(|l| match l.get_name() {
+                    Some(name) => format!("{} #{}", name, edition_size),
+                    None => default_name.clone(),
+                })
+                .chain(edition_config.get_one_of_ones().iter().map(|o| {
+                    o.get_name()
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|| default_name.clone())
+                }))
+                .collect::<BTreeSet<String>>();
+
+            for name in names {
+                let asset_name = cip25_asset_name(&name);
+                if asset_name.is_empty() || asset_name.len() > CIP25_MAX_ASSET_NAME_LENGTH {
+                    issues.push(ValidationIssue::error(format!(
+                        "CIP-25 asset name ({}) of ({}) must have 1 - {} bytes",
+                        asset_name, name, CIP25_MAX_ASSET_NAME_LENGTH
+                    )));
+                }
+            }
+        }
+        MetadataProfile::Erc721 => {}
+    }
+
+    issues
+}
+
+/// Checks that the images of the one of ones exist and their edition numbers are unique and
+/// within the edition size.
+pub fn check_one_of_ones(edition_config: &EditionConfiguration) -> Vec<ValidationIssue> {
+    let edition_size = edition_config.get_edition_size();
+    let mut editions = HashMap::new();
+    let mut issues = vec![];
+
+    for one_of_one in edition_config.get_one_of_ones() {
+        let image = one_of_one.get_image();
+        if !image.is_file() {
+            issues.push(ValidationIssue::error("One of one image doesn't exist").with_file(image));
+        } else {
+            // one of ones are saved as still images, an animation would lose all but one frame
+            match LayerFrames::read(image) {
+                Ok(frames) if frames.get_frames().len() > 1 => issues.push(
+                    ValidationIssue::error(format!(
+                        "One of one image is animated ({} frames), only still images are supported",
+                        frames.get_frames().len()
+                    ))
+                    .with_file(image),
+                ),
+                Ok(_) => {}
+                Err(err) => issues.push(
+                    ValidationIssue::error(format!("Couldn't decode one of one image: {:#}", err))
+                        .with_file(image),
+                ),
+            }
+        }
+
+        let edition = match one_of_one.get_edition() {
+            Some(edition) => edition,
+            None => continue,
+        };
+        if edition == 0 || edition > edition_size {
+            issues.push(
+                ValidationIssue::error(format!(
+                    "One of one edition #{} is out of the edition numbers 1 - {}",
+                    edition, edition_size
+                ))
+                .with_file(image),
+            );
+        }
+        if let Some(other) = editions.insert(edition, image) {
+            issues.push(
+                ValidationIssue::error(format!(
+                    "One of one edition #{} is already taken by ({})",
+                    edition,
+                    other.display()
+                ))
+                .with_file(image),
+            );
+        }
+    }
+
+    issues
+}
+
+/// Checks that the trait quotas can be met: the quota layers are picked exactly once by every
+/// layer configuration drawing them and the quotas fit into the editions drawing the layer.
+pub fn check_quotas(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+
+    for (layer, quotas) in edition_config.get_quotas() {
+        let file_names = match layers.get_file_names(layer) {
+            Some(file_names) => file_names,
+            None => {
+                issues.push(ValidationIssue::error("Quota layer doesn't exist").with_layer(layer));
+                continue;
+            }
+        };
+        for file in quotas.keys() {
+            if !file_names.contains(&file.as_str()) {
+                issues.push(
+                    ValidationIssue::error(format!("Quota file ({}) doesn't exist", file))
+                        .with_layer(layer),
+                );
+            }
+        }
+
+        let mut draws = 0;
+        let mut combinations = 0u128;
+        for (index, layer_config) in edition_config.get_layers().iter().enumerate() {
+            let lo = match layer_config
+                .get_order()
+                .iter()
+                .find(|lo| lo.get_name() == layer)
+            {
+                Some(lo) => lo,
+                None => continue,
+            };
+
+            if lo.get_pick_min() != 1 || lo.get_pick_max() != 1 || !lo.get_only_if().is_empty() {
+                issues.push(
+                    ValidationIssue::error(
+                        "Quota layer must be picked exactly once and without 'onlyIf' rules",
+                    )
+                    .with_layer_configuration(index)
+                    .with_layer(layer),
+                );
+            }
+            draws += layer_config.get_size();
+            combinations =
+                combinations.saturating_add(combinations_with_file(layers, layer_config, layer));
+        }
+
+        if draws == 0 {
+            issues.push(
+                ValidationIssue::error("Quota layer isn't part of any layer configuration")
+                    .with_layer(layer),
+            );
+            continue;
+        }
+
+        let sum = quotas.values().sum::<u32>();
+        let has_free_files = file_names.iter().any(|f| !quotas.contains_key(*f));
+        if sum > draws {
+            issues.push(
+                ValidationIssue::error(format!(
+                    "Quotas sum up to {}, but only {} editions draw the layer",
+                    sum, draws
+                ))
+                .with_layer(layer),
+            );
+        } else if sum < draws && !has_free_files {
+            issues.push(
+                ValidationIssue::error(format!(
+                    "Quotas sum up to {}, but all {} editions drawing the layer need one of its files",
+                    sum, draws
+                ))
+                .with_layer(layer),
+            );
+        }
+
+        for (file, quota) in quotas {
+            if *quota as u128 > combinations {
+                issues.push(
+                    ValidationIssue::error(format!(
+                        "Quota of {} for file ({}) exceeds the {} distinct editions with it",
+                        quota, file, combinations
+                    ))
+                    .with_layer(layer),
+                );
+            }
+        }
+    }
+
+    issues
+}
+
+/// Checks that the `requires` and `onlyIf` rules of every layer order only reference layers
+/// drawn before and files that exist, so they can be satisfied while walking the order.
+pub fn check_layer_rules(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+
+    for (config_index, layer_config) in edition_config.get_layers().iter().enumerate() {
+        let order = layer_config.get_order();
+
+        for (index, lo) in order.iter().enumerate() {
+            let file_names = match layers.get_file_names(lo.get_name()) {
+                Some(file_names) => file_names,
+                None => continue,
+            };
+            let issue = |message: String| {
+                ValidationIssue::error(message)
+                    .with_layer_configuration(config_index)
+                    .with_layer(lo.get_name())
+            };
+            let selectors = lo
+                .get_only_if()
+                .iter()
+                .chain(lo.get_requires().iter().map(|r| r.get_selector()));
+
+            for selector in selectors {
+                if !order[..index]
+                    .iter()
+                    .any(|o| o.get_name() == selector.get_layer())
+                {
+                    issues.push(issue(format!(
+                        "Layer ({}) depends on layer ({}), which is not drawn before it",
+                        lo.get_name(),
+                        selector.get_layer()
+                    )));
+                    continue;
+                }
+
+                let selector_files = match layers.get_file_names(selector.get_layer()) {
+                    Some(selector_files) => selector_files,
+                    None => continue,
+                };
+                if let Some(file) = selector.get_file() {
+                    if !selector_files.contains(&file) {
+                        issues.push(issue(format!(
+                            "Layer ({}) depends on file ({}), which doesn't exist in layer ({})",
+                            lo.get_name(),
+                            file,
+                            selector.get_layer()
+                        )));
+                    }
+                }
+            }
+
+            for requirement in lo.get_requires() {
+                let selector = requirement.get_selector();
+
+                for file in requirement.get_files().into_iter().flatten() {
+                    if !file_names.contains(&file.as_str()) {
+                        issues.push(issue(format!(
+                            "Layer ({}) requires file ({}), which doesn't exist in the layer",
+                            lo.get_name(),
+                            file
+                        )));
+                    }
+                }
+
+                if requirement.is_same_file() {
+                    let selector_files = layers
+                        .get_file_names(selector.get_layer())
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|f| selector.get_file().is_none_or(|s| s == *f));
+
+                    for file in selector_files {
+                        if !requirement.allows([(selector.get_layer(), file)], file)
+                            || !file_names.contains(&file)
+                        {
+                            issues.push(issue(format!(
+                                "Layer ({}) requires the same file as layer ({}), \
+                                but has no file ({}) which can be picked",
+                                lo.get_name(),
+                                selector.get_layer(),
+                                file
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+fn check_layer_order(layers: &Layers, layer_config: &LayerConfiguration) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+
+    for lo in layer_config.get_order() {
+        let issue = |message: String| ValidationIssue::error(message).with_layer(lo.get_name());
+
+        let file_count = match layers.get_file_names(lo.get_name()) {
+            Some(file_names) => file_names.len() as u32,
+            None => {
+                issues.push(issue(format!(
+                    "Couldn't find layer with name: {}",
+                    lo.get_name()
+                )));
+                continue;
+            }
+        };
+
+        if lo.get_pick_min() > lo.get_pick_max() {
+            issues.push(issue(format!(
+                "pickMin ({}) is greater than pickMax ({})",
+                lo.get_pick_min(),
+                lo.get_pick_max()
+            )));
+        }
+        if lo.get_pick_max() > file_count {
+            issues.push(issue(format!(
+                "pickMax ({}) is greater than the number of layer files ({})",
+                lo.get_pick_max(),
+                file_count
+            )));
+        }
+    }
+
+    issues
+}
+
+fn check_layer_files(
+    layers: &Layers,
+    layer_config: &LayerConfiguration,
+    (width, height): (u32, u32),
+) -> Vec<ValidationIssue> {
+    let files = layer_config
+        .get_order()
+        .iter()
+        .flat_map(|lo| {
+            layers
+                .get_file_paths(lo.get_name())
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |(p, t)| (lo.get_name(), p, t.or_else(|| lo.get_transform()).is_some()))
+        })
+        .collect::<Vec<(&str, &Path, bool)>>();
+
+    let decoded = files
+        .par_iter()
+        .map(|(layer, path, is_transformed)| {
+            LayerFrames::read(path)
+                .map(|frames| (*layer, *path, *is_transformed, frames.dimensions()))
+                .map_err(|err| {
+                    ValidationIssue::error(format!("Couldn't decode layer file: {:#}", err))
+                        .with_layer(layer)
+                        .with_file(path)
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let mut issues = vec![];
+    let mut dimensions = vec![];
+    for result in decoded {
+        match result {
+            // transformed layer files are placed explicitly, so they may have any dimensions
+            Ok((layer, path, false, d)) => dimensions.push((layer, path, d)),
+            Ok(_) => {}
+            Err(issue) => issues.push(issue),
+        }
+    }
+
+    // the most common dimensions are considered to be the intended ones
+    let mut counts: HashMap<(u32, u32), usize> = HashMap::new();
+    for (_, _, d) in &dimensions {
+        *counts.entry(*d).or_insert(0) += 1;
+    }
+    let expected = match counts.into_iter().max_by_key(|(d, count)| (*count, *d)) {
+        Some((expected, _)) => expected,
+        None => return issues,
+    };
+
+    for (layer, path, d) in &dimensions {
+        if *d != expected {
+            issues.push(
+                ValidationIssue::error(format!(
+                    "Inconsistent dimensions {}x{}px, most layer files have {}x{}px",
+                    d.0, d.1, expected.0, expected.1
+                ))
+                .with_layer(layer)
+                .with_file(path),
+            );
+        }
+    }
+
+    // layer files are resampled to the image size, which only distorts them on another aspect ratio
+    if expected.0 as u64 * height as u64 != expected.1 as u64 * width as u64 {
+        issues.push(ValidationIssue::warning(format!(
+            "Layer files have {}x{}px, resizing them to the image size of {}x{}px distorts them",
+            expected.0, expected.1, width, height
+        )));
+    }
+
+    issues
+}
+
+fn check_edition_size(
+    layers: &Layers,
+    edition_config: &EditionConfiguration,
+    layer_config: &LayerConfiguration,
+) -> Vec<ValidationIssue> {
+    let capacity = capacity(layers, edition_config, layer_config);
+
+    if capacity.is_exceeded() {
+        vec![ValidationIssue::error(format!(
+            "{}, the edition size can't be achieved",
+            capacity.describe()
+        ))]
+    } else if capacity.is_critical() {
+        vec![ValidationIssue::warning(format!(
+            "{}, expect a lot of duplicate retries",
+            capacity.describe()
+        ))]
+    } else {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{write, File};
+
+    use image::codecs::gif::GifEncoder;
+    use image::{Frame, ImageBuffer, Rgba};
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{read_layers, test_edition_config, TempDir};
+
+    const LAYERS: [(&str, &[&str]); 2] =
+        [("Background", &["Blue", "Red"]), ("Hat", &["Cap", "Crown"])];
+
+    fn assert_issues(issues: &[ValidationIssue], expected: &[&str]) {
+        let messages = issues
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>();
+
+        for expected in expected {
+            assert!(
+                messages.iter().any(|m| m.contains(expected)),
+                "missing issue ({}) in {:?}",
+                expected,
+                messages
+            );
+        }
+    }
+
+    #[test]
+    fn check_layer_order_and_files() {
+        let dir = TempDir::with_layers(
+            "validate_files",
+            &[("Background", &["Blue", "Red", "Green"])],
+        );
+        let layers_dir = dir.join("layers");
+        ImageBuffer::from_pixel(8, 8, Rgba([0u8, 0, 0, 255]))
+            .save(layers_dir.join("Background").join("Big.png"))
+            .unwrap();
+        write(layers_dir.join("Background").join("Broken.png"), "no png").unwrap();
+        let layers = read_layers(&layers_dir);
+        let edition_config = test_edition_config(json!({"layers": [{"size": 2, "order": [
+            {"name": "Background", "pickMin": 3, "pickMax": 9},
+            {"name": "Missing"}
+        ]}]}));
+
+        let report = validate(&layers, &edition_config, (4, 4));
+        assert!(!report.is_valid());
+        assert_issues(
+            report.get_issues(),
+            &[
+                "[layers #0] [Missing] Couldn't find layer with name: Missing",
+                "pickMax (9) is greater than the number of layer files (5)",
+                "Couldn't decode layer file",
+                "Inconsistent dimensions 8x8px, most layer files have 4x4px",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_layer_rules_references() {
+        let dir = TempDir::with_layers("validate_rules", &LAYERS);
+        let layers = read_layers(&dir.join("layers"));
+        let edition_config = test_edition_config(json!({"layers": [{"size": 1, "order": [
+            {"name": "Background", "onlyIf": [{"layer": "Hat"}]},
+            {"name": "Hat", "requires": [{"layer": "Background", "file": "Pink", "files": ["Tiara"]}]}
+        ]}]}));
+
+        assert_issues(
+            &check_layer_rules(&layers, &edition_config),
+            &[
+                "Layer (Background) depends on layer (Hat), which is not drawn before it",
+                "Layer (Hat) depends on file (Pink), which doesn't exist in layer (Background)",
+                "Layer (Hat) requires file (Tiara), which doesn't exist in the layer",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_metadata_profiles() {
+        let metaplex = test_edition_config(json!({"metadata": {
+            "profile": "metaplex",
+            "sellerFeeBasisPoints": 20000,
+            "creators": [{"address": "A", "share": 50}, {"address": "B", "share": 40}]
+        }}));
+        assert_issues(
+            &check_metadata(&metaplex),
+            &[
+                "Metadata sellerFeeBasisPoints exceeds 10000 (100%)",
+                "Metadata creator shares sum up to 90, expected 100",
+            ],
+        );
+
+        let cip25 = test_edition_config(json!({
+            "layers": [{"size": 10, "order": []}, {"size": 1, "order": [], "name": "x".repeat(31)}],
+            "metadata": {"profile": "cip25"}
+        }));
+        let issues = check_metadata(&cip25);
+        assert_eq!(issues.len(), 2);
+        assert_issues(
+            &issues,
+            &[
+                "Metadata profile cip25 requires a policyId",
+                "must have 1 - 32 bytes",
+            ],
+        );
+        assert!(check_metadata(&test_edition_config(json!({}))).is_empty());
+    }
+
+    #[test]
+    fn check_one_of_ones_editions() {
+        let dir = TempDir::new("validate_one_of_ones");
+        let image = dir.join("one.png");
+        ImageBuffer::from_pixel(4, 4, Rgba([0u8, 0, 0, 255]))
+            .save(&image)
+            .unwrap();
+        let animated = dir.join("animated.gif");
+        GifEncoder::new(File::create(&animated).unwrap())
+            .encode_frames(
+                (0..2u8)
+                    .map(|i| Frame::new(ImageBuffer::from_pixel(4, 4, Rgba([i * 100, 0, 0, 255])))),
+            )
+            .unwrap();
+        let corrupt = dir.join("corrupt.png");
+        write(&corrupt, b"not a png").unwrap();
+        let edition_config = test_edition_config(json!({
+            "layers": [{"size": 3, "order": []}],
+            "oneOfOnes": [
+                {"image": image, "edition": 2},
+                {"image": image, "edition": 2},
+                {"image": image, "edition": 10},
+                {"image": dir.join("missing.png")},
+                {"image": animated},
+                {"image": corrupt}
+            ]
+        }));
+
+        let issues = check_one_of_ones(&edition_config);
+        assert_eq!(issues.len(), 5);
+        assert_issues(
+            &issues,
+            &[
+                "One of one image doesn't exist",
+                "One of one edition #2 is already taken by",
+                "One of one edition #10 is out of the edition numbers 1 - 9",
+                "One of one image is animated (2 frames), only still images are supported",
+                "Couldn't decode one of one image",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_quotas_feasibility() {
+        let dir = TempDir::with_layers("validate_quotas", &LAYERS);
+        let layers = read_layers(&dir.join("layers"));
+        let edition_config = test_edition_config(json!({
+            "layers": [
+                {"size": 3, "order": [{"name": "Background"}, {"name": "Hat"}]},
+                {"size": 2, "order": [{"name": "Hat", "pickMax": 2}]}
+            ],
+            "quotas": {
+                "Background": {"Blue": 2, "Red": 2},
+                "Hat": {"Cap": 5, "Crown": 2},
+                "Eyes": {"Big": 1}
+            }
+        }));
+
+        assert_issues(
+            &check_quotas(&layers, &edition_config),
+            &[
+                "[Background] Quotas sum up to 4, but only 3 editions draw the layer",
+                "[Hat] Quotas sum up to 7, but only 5 editions draw the layer",
+                "[layers #1] [Hat] Quota layer must be picked exactly once",
+                "[Eyes] Quota layer doesn't exist",
+            ],
+        );
+    }
+}